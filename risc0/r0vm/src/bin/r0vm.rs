@@ -16,13 +16,19 @@ use std::default::Default;
 use std::{
     fs,
     io::Write,
+    net::TcpListener,
     path::{Path, PathBuf},
 };
 
 use anyhow::Result;
 use clap::Parser;
 use risc0_zkvm::{
-    prove::profiler::Profiler, MethodId, Prover, ProverOpts, Receipt, DEFAULT_METHOD_ID_LIMIT,
+    prove::{
+        coverage::Coverage,
+        profiler::{diff_folded, write_diff, Profiler},
+        TraceEvent,
+    },
+    MethodId, Prover, ProverOpts, Receipt, DEFAULT_METHOD_ID_LIMIT,
 };
 
 /// Runs a RISC-V ELF binary within the RISC Zero ZKVM.
@@ -69,6 +75,31 @@ struct Args {
     /// to read it.
     #[clap(long)]
     pprof_out: Option<PathBuf>,
+
+    /// Write the guest's profile to this file as folded stacks, as used by
+    /// Brendan Gregg's flamegraph tools.
+    #[clap(long)]
+    folded_out: Option<PathBuf>,
+
+    /// Write an SVG flamegraph of the guest's profile to this file.
+    #[clap(long)]
+    flamegraph_out: Option<PathBuf>,
+
+    /// Compare the guest's profile against folded stacks previously written
+    /// with --folded-out, and print the change in cycles per function.
+    #[clap(long)]
+    compare_folded: Option<PathBuf>,
+
+    /// Write line coverage of the guest's run to this file, in lcov
+    /// ".info" format.
+    #[clap(long)]
+    coverage_out: Option<PathBuf>,
+
+    /// Wait for a GDB connection on this port on localhost before running
+    /// the guest; attach with `target remote localhost:<port>`.  The
+    /// debugger can only write guest memory when the seal is skipped.
+    #[clap(long)]
+    gdb: Option<u16>,
 }
 
 fn read_method_id(verbose: u8, elf_file: &Path, method_id_file: Option<&Path>) -> Option<MethodId> {
@@ -166,38 +197,96 @@ fn main() {
         })
     };
 
-    let opts: ProverOpts =
+    let mut opts: ProverOpts =
         ProverOpts::default().with_skip_seal(args.skip_seal || args.receipt.is_none());
 
+    if let Some(port) = args.gdb {
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        eprintln!("Waiting for GDB to connect on port {port}");
+        let (stream, addr) = listener.accept().unwrap();
+        if args.verbose > 0 {
+            eprintln!("GDB connected from {addr}");
+        }
+        opts = opts.with_gdb(stream);
+    }
+
     let mut guest_prof: Option<Profiler> = None;
 
-    if args.pprof_out.is_some() {
+    if args.pprof_out.is_some()
+        || args.folded_out.is_some()
+        || args.flamegraph_out.is_some()
+        || args.compare_folded.is_some()
+    {
         guest_prof = Some(Profiler::new(args.elf.to_str().unwrap(), &elf_contents).unwrap());
     }
 
-    let proof = run_prover(
-        &elf_contents,
-        &method_id,
-        if let Some(ref mut profiler) = guest_prof {
-            opts.with_trace_callback(profiler.make_trace_callback())
-        } else {
-            opts
-        },
-        args.initial_input.as_ref().map(|input| {
-            let input_bytes = fs::read(input).unwrap();
-            if args.verbose > 0 {
-                eprintln!("Supplying {} bytes of initial input", input_bytes.len());
-            }
-            input_bytes
-        }),
-    );
+    let mut guest_cov: Option<Coverage> = None;
+
+    if args.coverage_out.is_some() {
+        guest_cov = Some(Coverage::new(&elf_contents).unwrap());
+    }
+
+    let proof = {
+        let mut prof_callback = guest_prof
+            .as_mut()
+            .map(|profiler| profiler.make_trace_callback());
+        let mut cov_callback = guest_cov
+            .as_mut()
+            .map(|coverage| coverage.make_trace_callback());
+        if prof_callback.is_some() || cov_callback.is_some() {
+            opts = opts.with_trace_callback(|event: TraceEvent| {
+                if let Some(ref mut callback) = cov_callback {
+                    callback(event.clone())?;
+                }
+                if let Some(ref mut callback) = prof_callback {
+                    callback(event)?;
+                }
+                Ok(())
+            });
+        }
+        run_prover(
+            &elf_contents,
+            &method_id,
+            opts,
+            args.initial_input.as_ref().map(|input| {
+                let input_bytes = fs::read(input).unwrap();
+                if args.verbose > 0 {
+                    eprintln!("Supplying {} bytes of initial input", input_bytes.len());
+                }
+                input_bytes
+            }),
+        )
+    };
 
     // Now that we're done with the prover, we can collect the guest profiling data.
     if let Some(ref mut profiler) = guest_prof.as_mut() {
         profiler.finalize();
-        let report = profiler.encode_to_vec();
-        fs::write(args.pprof_out.as_ref().unwrap(), &report)
-            .expect("Unable to write profiling output");
+        if let Some(pprof_out) = args.pprof_out.as_ref() {
+            let report = profiler.encode_to_vec();
+            fs::write(pprof_out, &report).expect("Unable to write profiling output");
+        }
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        if let Some(folded_out) = args.folded_out.as_ref() {
+            fs::write(folded_out, &folded).expect("Unable to write folded stacks");
+        }
+        if let Some(flamegraph_out) = args.flamegraph_out.as_ref() {
+            let mut svg = Vec::new();
+            profiler
+                .write_flamegraph(&args.elf.display().to_string(), &mut svg)
+                .unwrap();
+            fs::write(flamegraph_out, &svg).expect("Unable to write flamegraph");
+        }
+        if let Some(compare_folded) = args.compare_folded.as_ref() {
+            let before = fs::read_to_string(compare_folded).expect("Unable to read folded stacks");
+            let after = String::from_utf8(folded).unwrap();
+            let deltas = diff_folded(&before, &after).expect("Unable to compare profiles");
+            write_diff(&deltas, &mut std::io::stderr()).unwrap();
+        }
+    }
+    if let Some(ref coverage) = guest_cov {
+        fs::write(args.coverage_out.as_ref().unwrap(), coverage.to_lcov())
+            .expect("Unable to write coverage output");
     }
     let (receipt, output) = proof.expect("Run failed");
 
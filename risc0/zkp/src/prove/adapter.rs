@@ -17,7 +17,6 @@ use std::sync::{
     Mutex,
 };
 
-use rand::thread_rng;
 use rayon::prelude::*;
 
 use crate::{
@@ -143,10 +142,9 @@ where
             *value = value.valid_or_zero();
         }
         // Add random noise to end of accum and change invalid element to zero
-        let mut rng = thread_rng();
         for i in self.steps - ZK_CYCLES..self.steps {
             for j in 0..accum_size {
-                self.accum[j * self.steps + i] = F::Elem::random(&mut rng);
+                self.accum[j * self.steps + i] = F::Elem::random(&mut self.exec.rng);
             }
         }
     }
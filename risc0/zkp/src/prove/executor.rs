@@ -16,7 +16,7 @@ use core::cmp::max;
 
 use anyhow::{bail, Result};
 use log::debug;
-use rand::thread_rng;
+use rand::{rngs::StdRng, SeedableRng};
 
 use crate::{
     adapter::{CircuitDef, CircuitStepContext, CircuitStepHandler},
@@ -43,6 +43,8 @@ where
     pub halted: bool,
     max_po2: usize,
     pub cycle: usize,
+    // Source of the random padding added for zero-knowledge.
+    pub(crate) rng: StdRng,
 }
 
 impl<F, C, S> Executor<F, C, S>
@@ -73,9 +75,21 @@ where
             halted: false,
             max_po2,
             cycle: 0,
+            rng: StdRng::from_entropy(),
         }
     }
 
+    /// Generate the random padding which hides the execution trace from a
+    /// seeded RNG, so that proving the same execution twice produces the
+    /// same seal.
+    ///
+    /// This is only meant for tests and debugging: anyone who knows the
+    /// seed can remove the padding, so the resulting proof is not
+    /// zero-knowledge.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
     pub fn step(&mut self, code: &[F::Elem], needed_fini: usize) -> Result<bool> {
         // debug!("code: {:?}", code);
         let next_cycles = self.cycle + needed_fini + ZK_CYCLES;
@@ -142,14 +156,13 @@ where
         assert!(self.halted);
         assert_eq!(self.cycle, self.steps - ZK_CYCLES);
 
-        let mut rng = thread_rng();
         // Make code be all zeros of zk cycles, and data be random
         for i in self.cycle..self.steps {
             for j in 0..self.code_size {
                 self.code[j * self.steps + i] = F::Elem::ZERO;
             }
             for j in 0..self.data_size {
-                self.data[j * self.steps + i] = F::Elem::random(&mut rng);
+                self.data[j * self.steps + i] = F::Elem::random(&mut self.rng);
             }
         }
         // Do the verify cycles
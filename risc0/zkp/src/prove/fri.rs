@@ -21,7 +21,7 @@ use crate::{
     core::{log2_ceil, sha::Sha},
     field::{Elem, ExtElem},
     hal::{Buffer, Hal},
-    prove::{merkle::MerkleTreeProver, write_iop::WriteIOP},
+    prove::{merkle::MerkleTreeProver, write_iop::WriteIOP, ProveEvent},
     FRI_FOLD, FRI_MIN_DEGREE, INV_RATE, QUERIES,
 };
 
@@ -90,6 +90,7 @@ pub fn fri_prove<H: Hal, S: Sha, F>(
     hal: &H,
     iop: &mut WriteIOP<S>,
     coeffs: &H::BufferElem,
+    progress: &mut dyn FnMut(ProveEvent),
     mut f: F,
 ) where
     F: FnMut(&mut WriteIOP<S>, usize),
@@ -99,6 +100,10 @@ pub fn fri_prove<H: Hal, S: Sha, F>(
     let mut rounds = Vec::new();
     let mut coeffs = coeffs.clone();
     while coeffs.size() / ext_size > FRI_MIN_DEGREE {
+        progress(ProveEvent::FriRound {
+            round: rounds.len(),
+            degree: coeffs.size() / ext_size,
+        });
         let round = ProveRoundInfo::new(hal, iop, &coeffs);
         coeffs = round.coeffs.clone();
         rounds.push(round);
@@ -18,13 +18,15 @@ pub mod executor;
 mod fri;
 mod merkle;
 pub mod poly_group;
+mod progress;
 pub mod write_iop;
 
 use alloc::{vec, vec::Vec};
 
 use log::debug;
 
-use self::adapter::ProveAdapter;
+pub use self::progress::{ProveEvent, ProvePhase};
+use self::{adapter::ProveAdapter, progress::PhaseTimer};
 use crate::{
     adapter::{CircuitDef, CircuitStepHandler},
     core::{
@@ -49,13 +51,33 @@ where
     circuit.execute(&mut iop);
 }
 
-#[tracing::instrument(skip_all)]
 pub fn prove<'a, F, H, S, E, C, CS>(
     hal: &H,
     sha: &S,
     circuit: &mut ProveAdapter<'a, F, C, CS>,
     eval: &E,
 ) -> Vec<u32>
+where
+    F: Field,
+    H: Hal<Elem = F::Elem, ExtElem = F::ExtElem>,
+    S: Sha,
+    E: EvalCheck<H>,
+    C: CircuitDef<F>,
+    CS: CircuitStepHandler<F::Elem>,
+{
+    prove_with_progress(hal, sha, circuit, eval, &mut |_| {})
+}
+
+/// Like [prove], but reports the progress of each phase of proving to the
+/// given callback.
+#[tracing::instrument(skip_all)]
+pub fn prove_with_progress<'a, F, H, S, E, C, CS>(
+    hal: &H,
+    sha: &S,
+    circuit: &mut ProveAdapter<'a, F, C, CS>,
+    eval: &E,
+    progress: &mut dyn FnMut(ProveEvent),
+) -> Vec<u32>
 where
     F: Field,
     H: Hal<Elem = F::Elem, ExtElem = F::ExtElem>,
@@ -83,29 +105,44 @@ where
     let po2 = circuit.po2();
     assert!(po2 as usize <= MAX_CYCLES_PO2);
     let size = 1 << po2;
+    progress(ProveEvent::TraceSize {
+        po2: po2 as usize,
+        code_size,
+        data_size,
+        accum_size,
+    });
 
     // Make code + data PolyGroups + commit them
+    let timer = PhaseTimer::start(progress, ProvePhase::CodeCommit);
     let code_coeffs = make_coeffs(hal, circuit.get_code(), code_size);
     let code_group = PolyGroup::new(hal, &code_coeffs, code_size, size, "code");
     code_group.merkle.commit(&mut iop);
     debug!("codeGroup: {}", code_group.merkle.root());
+    timer.finish(progress);
 
+    let timer = PhaseTimer::start(progress, ProvePhase::DataCommit);
     let data_coeffs = make_coeffs(hal, circuit.get_data(), data_size);
     let data_group = PolyGroup::new(hal, &data_coeffs, data_size, size, "data");
     data_group.merkle.commit(&mut iop);
     debug!("dataGroup: {}", data_group.merkle.root());
+    timer.finish(progress);
 
+    let timer = PhaseTimer::start(progress, ProvePhase::Accumulate);
     circuit.accumulate(&mut iop);
+    timer.finish(progress);
 
     // Make the accum group + commit
+    let timer = PhaseTimer::start(progress, ProvePhase::AccumCommit);
     debug!("size = {size}, accumSize = {accum_size}");
     debug!("getAccum.size() = {}", circuit.get_accum().len());
     let accum_coeffs = make_coeffs(hal, circuit.get_accum(), accum_size);
     let accum_group = PolyGroup::new(hal, &accum_coeffs, accum_size, size, "accum");
     accum_group.merkle.commit(&mut iop);
     debug!("accumGroup: {}", accum_group.merkle.root());
+    timer.finish(progress);
 
     // Set the poly mix value
+    let timer = PhaseTimer::start(progress, ProvePhase::CheckPoly);
     let poly_mix = H::ExtElem::random(&mut iop.rng);
 
     // Now generate the check polynomial
@@ -140,6 +177,7 @@ where
     // by) are in Fp, Fp4 values act like simple vectors of Fp for the
     // purposes of interpolate/evaluate.
     hal.batch_interpolate_ntt(&check_poly, H::ExtElem::EXT_SIZE);
+    timer.finish(progress);
 
     // The next step is to convert the degree 4*n check polynomial into 4 degreen n
     // polynomials so that f(x) = g0(x^4) + g1(x^4) x + g2(x^4) x^2 + g3(x^4)
@@ -153,11 +191,14 @@ where
     // invRate*size to 16 polys of size, without actually doing anything.
 
     // Make the PolyGroup + add it to the IOP;
+    let timer = PhaseTimer::start(progress, ProvePhase::CheckCommit);
     let check_group = PolyGroup::new(hal, &check_poly, H::CHECK_SIZE, size, "check");
     check_group.merkle.commit(&mut iop);
     debug!("checkGroup: {}", check_group.merkle.root());
+    timer.finish(progress);
 
     // Now pick a value for Z
+    let timer = PhaseTimer::start(progress, ProvePhase::Mix);
     let z = H::ExtElem::random(&mut iop.rng);
     // #ifdef CIRCUIT_DEBUG
     //   if (badZ != Fp4(0)) {
@@ -317,17 +358,20 @@ where
 
     // Finally do the FRI protocol to prove the degree of the polynomial
     hal.batch_bit_reverse(&final_poly_coeffs, H::ExtElem::EXT_SIZE);
+    timer.finish(progress);
     debug!(
         "FRI-proof, size = {}",
         final_poly_coeffs.size() / H::ExtElem::EXT_SIZE
     );
 
-    fri_prove(hal, &mut iop, &final_poly_coeffs, |iop, idx| {
+    let timer = PhaseTimer::start(progress, ProvePhase::Fri);
+    fri_prove(hal, &mut iop, &final_poly_coeffs, progress, |iop, idx| {
         accum_group.merkle.prove(iop, idx);
         code_group.merkle.prove(iop, idx);
         data_group.merkle.prove(iop, idx);
         check_group.merkle.prove(iop, idx);
     });
+    timer.finish(progress);
 
     // Return final proof
     let proof = iop.proof;
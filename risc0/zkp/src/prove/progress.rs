@@ -0,0 +1,103 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Structured progress reporting for the prover.
+
+use std::time::{Duration, Instant};
+
+/// A phase of proving.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProvePhase {
+    /// Running the circuit to generate the execution trace.  This is run by
+    /// the caller before [super::prove_with_progress], so it's up to the
+    /// caller to report it.
+    Execute,
+    /// Committing to the code columns of the trace.
+    CodeCommit,
+    /// Committing to the data columns of the trace.
+    DataCommit,
+    /// Computing the accumulator columns of the trace.
+    Accumulate,
+    /// Committing to the accumulator columns of the trace.
+    AccumCommit,
+    /// Evaluating the constraints to compute the check polynomial.
+    CheckPoly,
+    /// Committing to the check polynomial.
+    CheckCommit,
+    /// Evaluating the polynomials at the random point and mixing them into
+    /// the final polynomial.
+    Mix,
+    /// Proving the degree of the final polynomial with FRI.
+    Fri,
+}
+
+/// An event reported while proving.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProveEvent {
+    /// A phase has started.
+    PhaseStarted {
+        /// The phase which started.
+        phase: ProvePhase,
+    },
+    /// A phase has finished.
+    PhaseFinished {
+        /// The phase which finished.
+        phase: ProvePhase,
+        /// The time spent in the phase.
+        elapsed: Duration,
+    },
+    /// The size of the trace being proven.
+    TraceSize {
+        /// The number of rows in the trace, as a power of 2.
+        po2: usize,
+        /// The number of code columns.
+        code_size: usize,
+        /// The number of data columns.
+        data_size: usize,
+        /// The number of accumulator columns.
+        accum_size: usize,
+    },
+    /// A round of FRI folding has started.
+    FriRound {
+        /// The index of the round, starting from 0.
+        round: usize,
+        /// The degree of the polynomial being folded.
+        degree: usize,
+    },
+}
+
+// Reports the start of a phase, and its elapsed time once it finishes.
+pub(crate) struct PhaseTimer {
+    phase: ProvePhase,
+    start: Instant,
+}
+
+impl PhaseTimer {
+    pub fn start(progress: &mut dyn FnMut(ProveEvent), phase: ProvePhase) -> Self {
+        progress(ProveEvent::PhaseStarted { phase });
+        Self {
+            phase,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn finish(self, progress: &mut dyn FnMut(ProveEvent)) {
+        progress(ProveEvent::PhaseFinished {
+            phase: self.phase,
+            elapsed: self.start.elapsed(),
+        });
+    }
+}
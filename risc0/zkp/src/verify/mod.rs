@@ -46,6 +46,7 @@ pub enum VerificationError {
     InvalidProof,
     JournalSealRootMismatch,
     SealJournalLengthMismatch { seal_len: usize, journal_len: usize },
+    Po2LimitExceeded { po2: usize, max_po2: usize },
 }
 
 impl fmt::Display for VerificationError {
@@ -71,6 +72,10 @@ impl fmt::Display for VerificationError {
                 f,
                 "Seal's output length ({seal_len}) does not match journal length ({journal_len})"
             ),
+            VerificationError::Po2LimitExceeded { po2, max_po2 } => write!(
+                f,
+                "Receipt po2 of {po2} exceeds the maximum allowed po2 of {max_po2}"
+            ),
         }
     }
 }
@@ -253,7 +258,34 @@ mod host {
     }
 }
 
-#[tracing::instrument(skip_all)]
+/// Checks that the outputs of a seal, combined into 32-bit words, commit to
+/// the given journal: the outputs hold the journal itself if it fits in a
+/// digest, or its digest otherwise, followed by its length in bytes.
+pub fn check_journal<S: Sha>(
+    sha: &S,
+    outputs: &[u32],
+    journal: &[u32],
+) -> Result<(), VerificationError> {
+    let output_len = outputs[DIGEST_WORDS] as usize;
+    if journal.len() * WORD_SIZE != output_len {
+        return Err(VerificationError::SealJournalLengthMismatch {
+            seal_len: output_len,
+            journal_len: journal.len() * WORD_SIZE,
+        });
+    }
+    if journal.len() <= DIGEST_WORDS {
+        if journal != &outputs[..journal.len()] {
+            return Err(VerificationError::JournalSealRootMismatch);
+        }
+    } else {
+        let journal_digest = sha.hash_words(journal);
+        if journal_digest.as_slice() != &outputs[..DIGEST_WORDS] {
+            return Err(VerificationError::JournalSealRootMismatch);
+        }
+    }
+    Ok(())
+}
+
 pub fn verify<'a, H, C, F>(
     hal: &'a H,
     circuit: &C,
@@ -265,6 +297,27 @@ where
     H: VerifyHal,
     C: CircuitInfo + TapsProvider,
     F: Fn(u32, &Digest) -> Result<(), VerificationError>,
+{
+    verify_with_outputs(hal, circuit, seal, check_code, |outputs| {
+        check_journal(hal.sha(), outputs, journal)
+    })
+}
+
+/// Like [verify], but leaves checking the outputs of the seal, combined into
+/// 32-bit words, to `check_outputs`.
+#[tracing::instrument(skip_all)]
+pub fn verify_with_outputs<'a, H, C, F, O>(
+    hal: &'a H,
+    circuit: &C,
+    seal: &'a [u32],
+    check_code: F,
+    check_outputs: O,
+) -> Result<(), VerificationError>
+where
+    H: VerifyHal,
+    C: CircuitInfo + TapsProvider,
+    F: Fn(u32, &Digest) -> Result<(), VerificationError>,
+    O: FnOnce(&[u32]) -> Result<(), VerificationError>,
 {
     if seal.len() == 0 {
         return Err(VerificationError::ReceiptFormatError);
@@ -280,43 +333,15 @@ where
     adapter.execute(&mut iop);
 
     if let Some(outputs) = adapter.out {
-        let result_length_index = 16;
         // Each element outputs are generated by the output ecall. The handler for the
         // output ecall splits the 32-bit value supplied to the ecall into two
-        // 16-bit chunks. Since each index of the journal contains all 32 bits
-        // of the output ecall value, we must shift and combine the two 16-bit
-        // values before comparing them to the journal.
-        let output_len = u32::from(outputs[result_length_index])
-            + u32::from(outputs[result_length_index + 1])
-                .checked_shl(16)
-                .unwrap();
-        if journal.len() * WORD_SIZE != output_len.try_into().unwrap() {
-            return Err(VerificationError::SealJournalLengthMismatch {
-                seal_len: output_len.try_into().unwrap(),
-                journal_len: journal.len() * WORD_SIZE,
-            });
-        }
-        if journal.len() <= DIGEST_WORDS {
-            for i in 0..journal.len() {
-                if journal[i]
-                    != u32::from(outputs[i * 2])
-                        + u32::from(outputs[i * 2 + 1]).checked_shl(16).unwrap()
-                {
-                    return Err(VerificationError::JournalSealRootMismatch);
-                }
-            }
-        } else {
-            let journal_digest = hal.sha().hash_words(journal);
-            let journal_hash = journal_digest.as_slice();
-            for i in 0..journal_hash.len() {
-                if journal_hash[i]
-                    != (u32::from(outputs[i * 2]))
-                        + u32::from(outputs[i * 2 + 1]).checked_shl(16).unwrap()
-                {
-                    return Err(VerificationError::JournalSealRootMismatch);
-                }
-            }
-        }
+        // 16-bit chunks, so we must shift and combine the two 16-bit values
+        // to recover the values supplied to the ecall.
+        let outputs: Vec<u32> = outputs
+            .chunks(2)
+            .map(|half| u32::from(half[0]) + u32::from(half[1]).checked_shl(16).unwrap())
+            .collect();
+        check_outputs(&outputs)?;
     }
 
     // Get the size
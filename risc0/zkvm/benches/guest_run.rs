@@ -18,6 +18,10 @@
 //! such, they do not generate seals, and these performance numbers
 //! are not indicitive of performance with cryptographically secure
 //! proofs.
+//!
+//! The `alloc` benchmarks use the default guest allocator.  To compare it
+//! with the allocator which reuses freed memory, run them again with
+//! `--features risc0-zkvm-methods/heap-free-list`.
 
 use std::time::{Duration, Instant};
 
@@ -35,7 +39,11 @@ use risc0_zkvm_methods::{
     BENCH_CONTENTS, BENCH_ID,
 };
 
-fn run_guest(spec: SpecWithIters) -> Duration {
+// The number of allocations made by each run of an `alloc` benchmark, which
+// is small enough for the default allocator not to run out of heap.
+const ALLOC_ITERS: u64 = 200;
+
+fn guest_prover(spec: SpecWithIters) -> Prover<'static> {
     let input_data: Vec<u32> = to_vec(&spec).unwrap();
 
     let mut prover = Prover::new_with_opts(
@@ -45,12 +53,22 @@ fn run_guest(spec: SpecWithIters) -> Duration {
     )
     .unwrap();
     prover.add_input_u32_slice(input_data.as_slice());
+    prover
+}
 
+fn run_guest(spec: SpecWithIters) -> Duration {
+    let mut prover = guest_prover(spec);
     let start = Instant::now();
     black_box(prover.run().unwrap());
     start.elapsed()
 }
 
+fn guest_cycles(spec: SpecWithIters) -> usize {
+    let mut prover = guest_prover(spec);
+    prover.run().unwrap();
+    prover.cycles
+}
+
 fn guest_iter(b: &mut Bencher, spec: BenchmarkSpec) {
     b.iter_custom(|iters| run_guest(SpecWithIters(spec.clone(), iters)))
 }
@@ -66,6 +84,9 @@ where
 }
 
 pub fn bench(c: &mut Criterion) {
+    // Cycle counts are logged, e.g. with RUST_LOG=info.
+    env_logger::try_init().ok();
+
     c.bench_function("simple_loop", move |b| {
         guest_iter(b, BenchmarkSpec::SimpleLoop)
     });
@@ -175,6 +196,25 @@ pub fn bench(c: &mut Criterion) {
         );
     }
     memcpy_group.finish();
+
+    let mut alloc_group = c.benchmark_group("alloc");
+    alloc_group
+        .sampling_mode(SamplingMode::Flat)
+        .sample_size(10);
+    for len in [16usize, 256, 4096, 65536] {
+        let spec = SpecWithIters(BenchmarkSpec::Alloc { len }, ALLOC_ITERS);
+        let cycles = guest_cycles(spec.clone());
+        let overhead = guest_cycles(SpecWithIters(BenchmarkSpec::Alloc { len }, 0));
+        log::info!(
+            "alloc/{len}: {} cycles per allocation",
+            (cycles - overhead) as u64 / ALLOC_ITERS
+        );
+        alloc_group.throughput(Throughput::Elements(ALLOC_ITERS));
+        alloc_group.bench_with_input(BenchmarkId::from_parameter(len), &spec, |b, spec| {
+            b.iter(|| run_guest(spec.clone()))
+        });
+    }
+    alloc_group.finish();
 }
 
 criterion_group!(name = benches;
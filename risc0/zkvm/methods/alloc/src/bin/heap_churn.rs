@@ -0,0 +1,41 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Allocates and frees a 64KB buffer and a growing vector the given number of
+//! times, more in total than fits in the heap, and commits the sum of the
+//! vectors' lengths.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use risc0_zkvm::guest::{env, memory_barrier};
+
+risc0_zkvm::entry!(main);
+
+pub fn main() {
+    let rounds: u32 = env::read();
+    let mut total = 0;
+    for round in 0..rounds {
+        let mut buf: Vec<u8> = Vec::with_capacity(64 * 1024);
+        buf.push(round as u8);
+        memory_barrier(&buf);
+        let small: Vec<u32> = (0..round % 100).collect();
+        total += small.len() as u32;
+    }
+    env::commit(&total);
+}
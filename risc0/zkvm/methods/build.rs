@@ -24,6 +24,11 @@ fn main() {
         return;
     }
 
+    let mut std_features = vec!["test_feature1".to_string(), "test_feature2".to_string()];
+    if env::var("CARGO_FEATURE_HEAP_FREE_LIST").is_ok() {
+        std_features.push("heap-free-list".to_string());
+    }
+
     let map = HashMap::from([
         (
             "risc0-zkvm-methods-guest",
@@ -37,10 +42,18 @@ fn main() {
             "risc0-zkvm-methods-std",
             GuestOptions {
                 code_limit: 14,
-                features: vec!["test_feature1".to_string(), "test_feature2".to_string()],
+                features: std_features,
                 std: true,
             },
         ),
+        (
+            "risc0-zkvm-methods-alloc",
+            GuestOptions {
+                code_limit: 22,
+                features: Vec::new(),
+                std: false,
+            },
+        ),
     ]);
 
     embed_methods_with_options(map);
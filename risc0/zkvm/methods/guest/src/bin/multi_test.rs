@@ -17,13 +17,27 @@
 #![no_main]
 #![no_std]
 
+extern crate alloc;
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::arch::asm;
 
+use digest::Digest as _;
 use risc0_zeroio::deserialize::Deserialize;
 use risc0_zkp::core::sha::{testutil::test_sha_impl, Digest, Sha};
-use risc0_zkvm::guest::{env, memory_barrier, sha::Impl as ShaImpl};
+use risc0_zkvm::guest::{
+    env,
+    fs::{self, File, SeekFrom},
+    memory_barrier,
+    sha::{Impl as ShaImpl, Sha256},
+};
 use risc0_zkvm_methods::multi_test::{MultiTestSpec, MultiTestSpecRef};
-use risc0_zkvm_platform::io::SENDRECV_CHANNEL_INITIAL_INPUT;
+use risc0_zkvm_platform::{
+    io::SENDRECV_CHANNEL_INITIAL_INPUT, memory::INPUT, syscall::sys_user, WORD_SIZE,
+};
 
 risc0_zkvm::entry!(main);
 
@@ -82,6 +96,9 @@ pub fn main() {
         MultiTestSpecRef::Fail(_) => {
             panic!("MultiTestSpec::Fail invoked");
         }
+        MultiTestSpecRef::IllegalInstruction(_) => unsafe {
+            asm!(".word 0x00000000");
+        },
         MultiTestSpecRef::ReadWriteMem(values) => {
             for (addr, value) in values.values().iter() {
                 if value != 0 {
@@ -98,6 +115,13 @@ pub fn main() {
             let digest = ShaImpl {}.hash_bytes(data.data());
             env::commit(&digest);
         }
+        MultiTestSpecRef::ShaStream(spec) => {
+            let mut hasher = Sha256::new();
+            for chunk in spec.data().chunks(spec.chunk_size() as usize) {
+                hasher.update(chunk);
+            }
+            env::commit(&hasher.finalize().as_slice());
+        }
         MultiTestSpecRef::SendRecv(sendrecv) => {
             let mut input: &[u8] = &[];
             let mut input_len: usize = 0;
@@ -109,5 +133,104 @@ pub fn main() {
                 input_len = input.len();
             }
         }
+        MultiTestSpecRef::UserSyscall(spec) => {
+            let mut buf = [0u32; 4];
+            let args = [
+                buf.as_mut_ptr() as u32,
+                (buf.len() * WORD_SIZE) as u32,
+                0,
+                0,
+                0,
+            ];
+            let (result, _) = unsafe { sys_user(spec.nr(), args) };
+            env::commit(&(result, buf));
+        }
+        MultiTestSpecRef::ReadStdin(spec) => {
+            let mut buf = [0u8; 4096];
+            let chunk = &mut buf[..spec.chunk_size() as usize];
+            let mut stdin = env::stdin();
+            let (mut total, mut sum) = (0u32, 0u32);
+            loop {
+                let nread = stdin.read(chunk);
+                if nread == 0 {
+                    break;
+                }
+                total += nread as u32;
+                for byte in &chunk[..nread] {
+                    sum = sum.wrapping_add(*byte as u32);
+                }
+            }
+            env::commit(&(total, sum));
+        }
+        MultiTestSpecRef::Random(spec) => {
+            let mut buf = [0u8; 64];
+            let buf = &mut buf[..spec.len() as usize];
+            getrandom::getrandom(buf).unwrap();
+            env::commit(&buf);
+        }
+        MultiTestSpecRef::ArgsEnv(_) => {
+            let args: Vec<_> = env::args().collect();
+            env::commit(&(args, env::var("FOO"), env::var("BAR")));
+        }
+        MultiTestSpecRef::ReadFiles(_) => {
+            let len = fs::metadata("data/table.txt").unwrap().len() as u32;
+            let mut table = File::open("/data/table.txt").unwrap();
+            table.seek(SeekFrom::Start(2)).unwrap();
+            let mut part = [0u8; 3];
+            assert_eq!(table.read(&mut part).unwrap(), 3);
+            let config = fs::read("config.txt").unwrap();
+            let entries: Vec<_> = fs::read_dir("data")
+                .unwrap()
+                .iter()
+                .map(|entry| (entry.file_name().to_string(), entry.is_dir()))
+                .collect();
+            let errors = [
+                File::open("missing").err() == Some(fs::Error::NotFound),
+                File::open("data").err() == Some(fs::Error::IsADirectory),
+                File::open("../escape").err() == Some(fs::Error::InvalidInput),
+            ];
+            env::commit(&(len, part, config, entries, errors));
+        }
+        MultiTestSpecRef::ReadZeroio(_) => {
+            let name: &str = env::read_zeroio::<String>();
+            let data: &[u8] = env::read_zeroio::<Vec<u8>>();
+            let sum: u32 = data.iter().map(|&byte| byte as u32).sum();
+            let addr = data.as_ptr() as usize;
+            let in_place = addr >= INPUT.start() && addr < INPUT.end();
+            env::commit(&(name, sum, in_place));
+        }
+        MultiTestSpecRef::Exit(spec) => {
+            env::commit(&spec.code());
+            env::exit(spec.code());
+        }
+        MultiTestSpecRef::Divide(spec) => {
+            let mut acc = 0x1234_5678u32;
+            for i in 1..=spec.count() {
+                let x = acc ^ i;
+                let shift = i % 32;
+                acc = acc
+                    .wrapping_add(x / i)
+                    .wrapping_add(x % i)
+                    .wrapping_add((x as i32).wrapping_div(-(i as i32)) as u32)
+                    .wrapping_add((x as i32).wrapping_rem(i as i32) as u32)
+                    .wrapping_add(x >> shift)
+                    .wrapping_add(((x as i32) >> shift) as u32);
+            }
+            env::commit(&acc);
+        }
+        MultiTestSpecRef::CycleCount(_) => {
+            env::commit(&(env::get_cycle_count() as u32));
+        }
+        MultiTestSpecRef::Checkpoint(spec) => {
+            let mut history = Vec::new();
+            let mut acc = 0x1234_5678u32;
+            for i in 0..spec.rounds() {
+                acc = acc.rotate_left(5) ^ i.wrapping_mul(0x9e37_79b9);
+                history.push(acc);
+                env::commit(&i);
+                env::checkpoint();
+            }
+            env::commit(&history);
+        }
     }
 }
@@ -33,6 +33,9 @@ pub enum BenchmarkSpec {
     Memset {
         len: usize,
     },
+    Alloc {
+        len: usize,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
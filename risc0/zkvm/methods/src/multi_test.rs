@@ -27,9 +27,17 @@ pub enum MultiTestSpec {
     ShaDigest {
         data: Vec<u8>,
     },
+    /// Hashes the data with `guest::sha::Sha256`, passing it in chunks of the
+    /// given size, and commits the hash.
+    ShaStream {
+        data: Vec<u8>,
+        chunk_size: u32,
+    },
     EventTrace,
     Profiler,
     Fail,
+    /// Executes an all-zero instruction word, which is illegal.
+    IllegalInstruction,
     ReadWriteMem {
         /// Tuples of (address, value). Zero means read the value and
         /// output it; nonzero means write that value.
@@ -39,4 +47,47 @@ pub enum MultiTestSpec {
         channel_id: u32,
         count: u32,
     },
+    /// Invokes the given user-defined syscall with a buffer for the host to
+    /// fill, and commits the result along with the buffer.
+    UserSyscall {
+        nr: u32,
+    },
+    /// Reads stdin in chunks of the given size, and commits the number of
+    /// bytes read along with their wrapping sum.
+    ReadStdin {
+        chunk_size: u32,
+    },
+    /// Fills a buffer of the given size (at most 64 bytes) using
+    /// `getrandom`, and commits it.
+    Random {
+        len: u32,
+    },
+    /// Commits the guest's command-line arguments, followed by the values of
+    /// the environment variables `FOO` and `BAR`.
+    ArgsEnv,
+    /// Reads files from the host: the size of `data/table.txt`, the 3 bytes
+    /// at offset 2 of it, all of `config.txt`, the entries of `data`, and
+    /// whether opening `missing`, `data` and `../escape` failed as expected.
+    ReadFiles,
+    /// Reads a `String` and a `Vec<u8>` with `env::read_zeroio`, and commits
+    /// the string, the sum of the bytes, and whether the bytes were read in
+    /// place from the input region.
+    ReadZeroio,
+    /// Commits the exit code, then exits with it.
+    Exit {
+        code: u32,
+    },
+    /// Runs the given number of rounds of division, remainder and right
+    /// shifts, and commits the result.
+    Divide {
+        count: u32,
+    },
+    /// Commits the cycle count reported by the host.
+    CycleCount,
+    /// Runs the given number of rounds, committing each round's number and
+    /// calling `env::checkpoint` after it, then commits a value computed on
+    /// the heap along the way.
+    Checkpoint {
+        rounds: u32,
+    },
 }
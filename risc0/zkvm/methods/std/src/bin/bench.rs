@@ -60,5 +60,12 @@ pub fn main() {
                 memory_barrier(&dst_slice);
             }
         }
+        BenchmarkSpec::Alloc { len } => {
+            for i in 0..iters {
+                let mut buf: Vec<u8> = Vec::with_capacity(len);
+                buf.push(i as u8);
+                memory_barrier(&buf);
+            }
+        }
     }
 }
@@ -45,3 +45,44 @@ pub fn zkvm_abi_write_stderr(buf: &[u8]) {
         syscall::sys_io(io::SENDRECV_CHANNEL_STDERR, buf.as_ptr(), buf.len());
     }
 }
+
+// Command-line arguments and environment variables, for the standard
+// library's `std::env` on guests built with it.
+#[no_mangle]
+pub fn zkvm_abi_argc() -> usize {
+    unsafe { syscall::sys_argc() }
+}
+
+// Copies as much of the argument as fits into `buf`, returning its full
+// length.
+#[no_mangle]
+pub fn zkvm_abi_argv(index: usize, buf: &mut [u8]) -> usize {
+    unsafe { syscall::sys_argv(index, buf.as_mut_ptr(), buf.len()) }
+}
+
+// Copies as much of the variable's value as fits into `buf`, returning its
+// full length, or None if it isn't set.
+#[no_mangle]
+pub fn zkvm_abi_getenv(name: &[u8], buf: &mut [u8]) -> Option<usize> {
+    let len =
+        unsafe { syscall::sys_getenv(name.as_ptr(), name.len(), buf.as_mut_ptr(), buf.len()) };
+    if len == syscall::ENV_VAR_UNSET {
+        None
+    } else {
+        Some(len)
+    }
+}
+
+// Sends a request to the host's read-only filesystem, for the standard
+// library's `std::fs` on guests built with it.  See [io::fs] for the
+// protocol.
+#[no_mangle]
+pub fn zkvm_abi_fs_request(request: &[u8]) -> &'static [u8] {
+    unsafe { syscall::sys_io(io::SENDRECV_CHANNEL_FS, request.as_ptr(), request.len()) }
+}
+
+// Reads from a file opened with [zkvm_abi_fs_request].
+#[no_mangle]
+pub fn zkvm_abi_fs_read(fd: u32, buf: &mut [u8]) -> usize {
+    unsafe { syscall::sys_read(fd, buf.as_mut_ptr(), buf.len()) }
+}
@@ -16,6 +16,49 @@
 pub const SENDRECV_CHANNEL_INITIAL_INPUT: u32 = 0;
 pub const SENDRECV_CHANNEL_STDOUT: u32 = 1;
 pub const SENDRECV_CHANNEL_STDERR: u32 = 2;
+pub const SENDRECV_CHANNEL_FS: u32 = 3;
+/// Each exchange on this channel replies with the next value the host added
+/// with `Prover::add_input_zeroio`, serialized with `risc0_zeroio`.
+pub const SENDRECV_CHANNEL_ZEROIO_INPUT: u32 = 4;
+
+// Host file descriptors which can be read with sys_read.  Files opened
+// through SENDRECV_CHANNEL_FS are given descriptors after these.
+pub const FILENO_STDIN: u32 = 0;
+
+/// The protocol spoken over [SENDRECV_CHANNEL_FS], which gives the guest
+/// read-only access to files supplied by the host.
+///
+/// Each request is a little-endian `u32` operation followed by its
+/// arguments; paths come last and take up the rest of the request.  Except
+/// for closes, each reply starts with a `u32` status, which is followed by
+/// the results if the status is [fs::STATUS_OK]:
+///
+/// * [fs::OP_OPEN] `path` -> `fd`, `len: u64`.  The file's contents are then
+///   read with `sys_read` on `fd`.
+/// * [fs::OP_SEEK] `fd`, `offset: u64` -> nothing.
+/// * [fs::OP_CLOSE] `fd`, which has no reply at all.
+/// * [fs::OP_METADATA] `path` -> `kind`, `len: u64`.
+/// * [fs::OP_READ_DIR] `path` -> `count`, then for each entry `kind`,
+///   `name_len` and the name, padded to a whole number of words.
+///
+/// `u64` values are sent as two `u32` words, low word first.
+pub mod fs {
+    pub const OP_OPEN: u32 = 0;
+    pub const OP_SEEK: u32 = 1;
+    pub const OP_CLOSE: u32 = 2;
+    pub const OP_METADATA: u32 = 3;
+    pub const OP_READ_DIR: u32 = 4;
+
+    pub const STATUS_OK: u32 = 0;
+    pub const STATUS_NOT_FOUND: u32 = 1;
+    pub const STATUS_NOT_A_DIRECTORY: u32 = 2;
+    pub const STATUS_IS_A_DIRECTORY: u32 = 3;
+    pub const STATUS_INVALID: u32 = 4;
+    pub const STATUS_IO_ERROR: u32 = 5;
+
+    pub const KIND_FILE: u32 = 0;
+    pub const KIND_DIR: u32 = 1;
+}
 
 #[repr(C)]
 pub struct SliceDescriptor {
@@ -36,6 +36,18 @@ pub mod nr {
     pub const SYS_COMMIT: u32 = 3;
     pub const SYS_CYCLE_COUNT: u32 = 4;
     pub const SYS_COMPUTE_POLY: u32 = 5;
+    pub const SYS_READ: u32 = 6;
+    pub const SYS_RANDOM: u32 = 7;
+    pub const SYS_ARGC: u32 = 8;
+    pub const SYS_ARGV: u32 = 9;
+    pub const SYS_GETENV: u32 = 10;
+    pub const SYS_PAUSE_REQUESTED: u32 = 11;
+    pub const SYS_PAUSE: u32 = 12;
+    pub const SYS_RESUME: u32 = 13;
+
+    /// Syscall numbers at or above this value are reserved for user-defined
+    /// syscalls, which are serviced by custom handlers on the host.
+    pub const SYS_USER_BASE: u32 = 0x1000;
 }
 
 pub mod reg_abi {
@@ -129,9 +141,9 @@ pub unsafe fn sys_io(channel: u32, buf_ptr: *const u8, buf_len: usize) -> &'stat
         );
         let out_nwords = (out_nbytes + WORD_SIZE - 1) / WORD_SIZE;
         let read_end = read_ptr.checked_add(out_nwords * WORD_SIZE).unwrap();
-        // if read_end > memory::INPUT.end() {
-        //     panic!("host_recv overran input buffer with {nwords} word read");
-        // }
+        if read_end > memory::INPUT.end() {
+            panic!("sys_io overran input buffer with {out_nwords} word read");
+        }
         *read_ptr = read_end;
         core::slice::from_raw_parts(out_ptr, out_nbytes)
     }
@@ -139,6 +151,186 @@ pub unsafe fn sys_io(channel: u32, buf_ptr: *const u8, buf_len: usize) -> &'stat
     unimplemented!()
 }
 
+/// Reads up to `nbytes` bytes from the given host file descriptor into `buf`,
+/// returning the number of bytes read.  Returns 0 once the end of the input
+/// has been reached.
+#[inline(always)]
+pub unsafe fn sys_read(fd: u32, buf: *mut u8, nbytes: usize) -> usize {
+    #[cfg(target_os = "zkvm")]
+    {
+        let nread: usize;
+        asm!(
+            "ecall",
+            in("t0") ecall::SOFTWARE,
+            in("a7") nr::SYS_READ,
+            inout("a0") fd => nread,
+            inout("a1") buf => _,
+            in("a2") nbytes,
+        );
+        nread
+    }
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
+/// Fills the given buffer with random bytes from the host.
+///
+/// The host is trusted to provide these; nothing about the proof attests that
+/// they are random.
+#[inline(always)]
+pub unsafe fn sys_random(buf: *mut u8, nbytes: usize) {
+    #[cfg(target_os = "zkvm")]
+    asm!(
+        "ecall",
+        in("t0") ecall::SOFTWARE,
+        in("a7") nr::SYS_RANDOM,
+        inout("a0") buf => _,
+        inout("a1") nbytes => _,
+    );
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
+/// Returns the number of command-line arguments the host gave the guest.
+#[inline(always)]
+pub unsafe fn sys_argc() -> usize {
+    #[cfg(target_os = "zkvm")]
+    {
+        let argc: usize;
+        asm!(
+            "ecall",
+            in("t0") ecall::SOFTWARE,
+            in("a7") nr::SYS_ARGC,
+            out("a0") argc,
+            out("a1") _,
+        );
+        argc
+    }
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
+/// Copies as much of the command-line argument at `index` as fits into the
+/// given buffer, and returns the argument's full length in bytes.
+#[inline(always)]
+pub unsafe fn sys_argv(index: usize, buf: *mut u8, buf_len: usize) -> usize {
+    #[cfg(target_os = "zkvm")]
+    {
+        let len: usize;
+        asm!(
+            "ecall",
+            in("t0") ecall::SOFTWARE,
+            in("a7") nr::SYS_ARGV,
+            inout("a0") index => len,
+            inout("a1") buf => _,
+            in("a2") buf_len,
+        );
+        len
+    }
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
+/// Returned by [sys_getenv] for a variable which is not set.
+pub const ENV_VAR_UNSET: usize = usize::MAX;
+
+/// Copies as much of the value of the named environment variable as fits
+/// into the given buffer, and returns the value's full length in bytes, or
+/// [ENV_VAR_UNSET] if the host did not set it.
+#[inline(always)]
+pub unsafe fn sys_getenv(name: *const u8, name_len: usize, buf: *mut u8, buf_len: usize) -> usize {
+    #[cfg(target_os = "zkvm")]
+    {
+        let len: usize;
+        asm!(
+            "ecall",
+            in("t0") ecall::SOFTWARE,
+            in("a7") nr::SYS_GETENV,
+            inout("a0") name => len,
+            inout("a1") name_len => _,
+            in("a2") buf,
+            in("a3") buf_len,
+        );
+        len
+    }
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
+/// Returns whether the host wants the guest to pause, so the rest of its
+/// execution can be proven in another segment.
+#[inline(always)]
+pub unsafe fn sys_pause_requested() -> bool {
+    #[cfg(target_os = "zkvm")]
+    {
+        let requested: usize;
+        asm!(
+            "ecall",
+            in("t0") ecall::SOFTWARE,
+            in("a7") nr::SYS_PAUSE_REQUESTED,
+            out("a0") requested,
+            out("a1") _,
+        );
+        requested != 0
+    }
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
+/// Tells the host that the guest is about to pause, passing the regions of
+/// memory which hold its state as pairs of start address and length in bytes,
+/// and the digest of that state.  The host saves the regions, to restore them
+/// with [sys_resume] in the next segment.
+#[inline(always)]
+pub unsafe fn sys_pause(
+    layout: *const u32,
+    layout_words: usize,
+    digest: *const [u32; DIGEST_WORDS],
+) {
+    #[cfg(target_os = "zkvm")]
+    asm!(
+        "ecall",
+        in("t0") ecall::SOFTWARE,
+        in("a7") nr::SYS_PAUSE,
+        inout("a0") layout => _,
+        inout("a1") layout_words => _,
+        in("a2") digest,
+    );
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
+/// Asks the host whether this segment resumes a paused guest.  If so, the
+/// host writes the layout the guest passed to [sys_pause] into `layout`,
+/// restores the regions of memory it describes, and returns true.
+///
+/// The host writes to memory the guest has not yet touched, so this must be
+/// called before the guest uses any of the memory it saved.
+#[inline(always)]
+pub unsafe fn sys_resume(layout: *mut u32, layout_words: usize) -> bool {
+    #[cfg(target_os = "zkvm")]
+    {
+        let resumed: usize;
+        asm!(
+            "ecall",
+            in("t0") ecall::SOFTWARE,
+            in("a7") nr::SYS_RESUME,
+            inout("a0") layout => resumed,
+            inout("a1") layout_words => _,
+        );
+        resumed != 0
+    }
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
+/// Returns the end of the data the host has written to the input region in
+/// reply to [sys_io] and [sys_compute_poly].
+#[cfg(target_os = "zkvm")]
+pub fn input_end() -> usize {
+    unsafe { *READ_PTR.get() }
+}
+
 #[inline(always)]
 pub unsafe fn sys_commit(buf_ptr: *const u32, buf_len: usize) {
     #[cfg(target_os = "zkvm")]
@@ -173,6 +365,30 @@ pub unsafe fn sys_cycle_count() -> usize {
     unimplemented!()
 }
 
+/// Invokes a user-defined syscall, passing the given arguments in registers
+/// `a0` through `a4`.  Returns the values placed in `a0` and `a1` by the host.
+#[inline(always)]
+pub unsafe fn sys_user(nr: u32, args: [u32; 5]) -> (u32, u32) {
+    #[cfg(target_os = "zkvm")]
+    {
+        let a0: u32;
+        let a1: u32;
+        asm!(
+            "ecall",
+            in("t0") ecall::SOFTWARE,
+            in("a7") nr,
+            inout("a0") args[0] => a0,
+            inout("a1") args[1] => a1,
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+        );
+        (a0, a1)
+    }
+    #[cfg(not(target_os = "zkvm"))]
+    unimplemented!()
+}
+
 #[inline(always)]
 pub unsafe fn sys_ffpu(code: &[u32], args: &[*mut u32]) {
     #[cfg(target_os = "zkvm")]
@@ -223,13 +439,18 @@ pub unsafe fn sys_compute_poly(
     unimplemented!()
 }
 
+/// Halts the guest, passing the kind of exit and the exit code to the host
+/// in a0 and a1.  The circuit doesn't read them; to be proven, they must also
+/// be written to the outputs, as `risc0_zkvm::guest::env::exit` does.
 #[inline(always)]
-pub unsafe fn sys_halt() {
+pub unsafe fn sys_halt(exit_kind: u32, exit_code: u32) {
     #[cfg(target_os = "zkvm")]
     {
         asm!(
             "ecall",
             in("t0") ecall::HALT,
+            in("a0") exit_kind,
+            in("a1") exit_code,
         );
         unreachable!();
     }
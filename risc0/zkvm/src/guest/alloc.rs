@@ -12,12 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! The guest's global allocator.
+//!
+//! By default, the guest uses a bump allocator which never frees memory.
+//! With the `heap-free-list` feature, it instead uses [FreeListAlloc], which
+//! reuses freed blocks at the cost of a few more cycles per allocation.
+
 use core::{
     alloc::{GlobalAlloc, Layout},
     cell::UnsafeCell,
+    ptr::null_mut,
 };
 
-use risc0_zkvm_platform::{abi, memory, WORD_SIZE};
+use risc0_zkvm_platform::{abi, WORD_SIZE};
 
 struct BumpPointerAlloc;
 
@@ -39,6 +46,140 @@ unsafe impl GlobalAlloc for BumpPointerAlloc {
     }
 }
 
+// The smallest and largest size classes, as powers of 2.  The smallest block
+// must be able to hold a [FreeBlock].
+const MIN_CLASS: usize = 3;
+const MAX_CLASS: usize = 12;
+const NUM_CLASSES: usize = MAX_CLASS - MIN_CLASS + 1;
+const LARGE_BLOCK: usize = 1 << MAX_CLASS;
+
+// The header written at the start of a block while it is free.
+struct FreeBlock {
+    next: *mut FreeBlock,
+    size: usize,
+}
+
+struct FreeLists {
+    classes: [*mut FreeBlock; NUM_CLASSES],
+    large: *mut FreeBlock,
+}
+
+/// An allocator which keeps a free list for each power of 2 size class up to
+/// 4KB.  Smaller allocations are rounded up to their size class and reuse
+/// freed blocks of that class.  Larger ones are rounded up to a multiple of
+/// 4KB and reuse the first large enough freed block, splitting it if it is
+/// bigger than needed.  Freed blocks are never coalesced or returned to the
+/// heap.
+struct FreeListAlloc(UnsafeCell<FreeLists>);
+
+// SAFETY: The guest is single threaded.
+unsafe impl Sync for FreeListAlloc {}
+
+impl FreeListAlloc {
+    const fn new() -> Self {
+        FreeListAlloc(UnsafeCell::new(FreeLists {
+            classes: [null_mut(); NUM_CLASSES],
+            large: null_mut(),
+        }))
+    }
+}
+
+// Returns the size class of a layout, or None if it needs a large block.
+fn size_class(layout: &Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align()).max(1 << MIN_CLASS);
+    (size <= LARGE_BLOCK).then(|| size.next_power_of_two().trailing_zeros() as usize - MIN_CLASS)
+}
+
+fn class_size(class: usize) -> usize {
+    1 << (class + MIN_CLASS)
+}
+
+fn large_size(layout: &Layout) -> usize {
+    (layout.size() + LARGE_BLOCK - 1) & !(LARGE_BLOCK - 1)
+}
+
+// Takes `size` bytes which have never been allocated from the heap, aligned
+// to `align`, which must be at least a word.
+#[cfg(target_os = "zkvm")]
+unsafe fn take_from_heap(size: usize, align: usize) -> *mut u8 {
+    let next = abi::zkvm_abi_alloc_words(0) as usize;
+    let padding = next.wrapping_neg() & (align - 1);
+    abi::zkvm_abi_alloc_words(padding / WORD_SIZE);
+    abi::zkvm_abi_alloc_words(size / WORD_SIZE) as *mut u8
+}
+
 #[cfg(target_os = "zkvm")]
+unsafe impl GlobalAlloc for FreeListAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let lists = &mut *self.0.get();
+        if let Some(class) = size_class(&layout) {
+            let head = &mut lists.classes[class];
+            if head.is_null() {
+                return take_from_heap(class_size(class), class_size(class));
+            }
+            let block = *head;
+            *head = (*block).next;
+            return block as *mut u8;
+        }
+
+        let size = large_size(&layout);
+        let mut link: *mut *mut FreeBlock = &mut lists.large;
+        while !(*link).is_null() {
+            let block = *link;
+            if (*block).size >= size && block as usize & (layout.align() - 1) == 0 {
+                let rest = (*block).size - size;
+                if rest == 0 {
+                    *link = (*block).next;
+                } else {
+                    let tail = (block as *mut u8).add(size) as *mut FreeBlock;
+                    tail.write(FreeBlock {
+                        next: (*block).next,
+                        size: rest,
+                    });
+                    *link = tail;
+                }
+                return block as *mut u8;
+            }
+            link = &mut (*block).next;
+        }
+        take_from_heap(size, layout.align().max(LARGE_BLOCK))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let lists = &mut *self.0.get();
+        let block = ptr as *mut FreeBlock;
+        let head = match size_class(&layout) {
+            Some(class) => &mut lists.classes[class],
+            None => &mut lists.large,
+        };
+        block.write(FreeBlock {
+            next: *head,
+            size: large_size(&layout),
+        });
+        *head = block;
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let fits = match (size_class(&layout), size_class(&new_layout)) {
+            (Some(old), Some(new)) => old == new,
+            (None, None) => large_size(&layout) == large_size(&new_layout),
+            _ => false,
+        };
+        if fits {
+            return ptr;
+        }
+        let new_ptr = self.alloc(new_layout);
+        core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+        self.dealloc(ptr, layout);
+        new_ptr
+    }
+}
+
+#[cfg(all(target_os = "zkvm", not(feature = "heap-free-list")))]
 #[global_allocator]
 static HEAP: BumpPointerAlloc = BumpPointerAlloc;
+
+#[cfg(all(target_os = "zkvm", feature = "heap-free-list"))]
+#[global_allocator]
+static HEAP: FreeListAlloc = FreeListAlloc::new();
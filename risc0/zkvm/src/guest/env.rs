@@ -14,19 +14,31 @@
 
 //! Functions for interacting with the host environment.
 
-use core::{cell::UnsafeCell, mem::MaybeUninit, ptr, slice};
+use alloc::{string::String, vec};
+use core::{
+    cell::UnsafeCell,
+    mem::{self, MaybeUninit},
+    ptr, slice,
+};
 
-use risc0_zkp::core::sha::{Digest, SHA256_INIT};
+use risc0_zkp::core::sha::{Digest, DIGEST_WORDS, SHA256_INIT};
 use risc0_zkvm_platform::{
-    io::{SENDRECV_CHANNEL_INITIAL_INPUT, SENDRECV_CHANNEL_STDOUT},
+    io::{
+        FILENO_STDIN, SENDRECV_CHANNEL_INITIAL_INPUT, SENDRECV_CHANNEL_STDOUT,
+        SENDRECV_CHANNEL_ZEROIO_INPUT,
+    },
     memory,
-    syscall::{sys_commit, sys_cycle_count, sys_halt, sys_io, sys_log, sys_output},
+    syscall::{
+        sys_argc, sys_argv, sys_commit, sys_cycle_count, sys_getenv, sys_halt, sys_io, sys_log,
+        sys_output, sys_read, ENV_VAR_UNSET,
+    },
     WORD_SIZE,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    guest::{memory_barrier, sha},
+    guest::{memory_barrier, segment, sha},
+    receipt::{ExitCode, EXIT_CODE_FLAG, RESUMED_FLAG},
     serde::{Deserializer, Serializer, Slice},
 };
 
@@ -81,8 +93,54 @@ pub(crate) fn init() {
     ENV.init(Env::new());
 }
 
-pub(crate) fn finalize(result: *mut usize) {
-    ENV.get().finalize(result);
+pub(crate) fn finalize(result: *mut usize, exit_code: ExitCode) {
+    ENV.get().finalize(result, exit_code, None);
+}
+
+// Halts a guest which has saved its state to be resumed in a later segment,
+// committing the digest of that state.
+pub(crate) fn pause(post_state: &Digest) -> ! {
+    extern "C" {
+        static mut __result: usize;
+    }
+    let result = unsafe { ptr::addr_of_mut!(__result) };
+    ENV.get()
+        .finalize(result, ExitCode::Paused, Some(post_state));
+    unreachable!()
+}
+
+// Returns the length of the journal in words.
+pub(crate) fn commit_len() -> usize {
+    ENV.get().commit_len
+}
+
+/// Halts the guest with the given exit code, committing it along with the
+/// journal.  The exit code is included in the receipt, so a guest can prove
+/// an outcome such as rejecting its input.
+///
+/// Returning from the guest's main function is the same as exiting with 0.
+pub fn exit(exit_code: u32) -> ! {
+    halt(ExitCode::Halted(exit_code))
+}
+
+// Halts the guest after a panic, so the receipt proves that the guest
+// panicked along with what it committed beforehand.
+pub(crate) fn fault(msg: &str) -> ! {
+    static mut FAULTED: bool = false;
+    // A panic while halting can't be proven, so give up on the receipt.
+    if unsafe { mem::replace(&mut FAULTED, true) } {
+        crate::guest::abort(msg)
+    }
+    log(msg);
+    halt(ExitCode::Faulted)
+}
+
+fn halt(exit_code: ExitCode) -> ! {
+    extern "C" {
+        static mut __result: usize;
+    }
+    finalize(unsafe { ptr::addr_of_mut!(__result) }, exit_code);
+    unreachable!()
 }
 
 /// Exchanges data with the host, returning the data from the host
@@ -102,6 +160,14 @@ pub fn read<T: Deserialize<'static>>() -> T {
     ENV.get().read()
 }
 
+/// Read the next value the host added with `Prover::add_input_zeroio`.
+///
+/// Unlike [read], this doesn't copy or deserialize the value; the returned
+/// reference type accesses it where the host wrote it in the input region.
+pub fn read_zeroio<T: risc0_zeroio::Deserialize<'static>>() -> T::RefType {
+    T::deserialize_from(send_recv_as_u32(SENDRECV_CHANNEL_ZEROIO_INPUT, &[]))
+}
+
 /// Write private data to the host.
 pub fn write<T: Serialize>(data: &T) {
     ENV.get().write(data);
@@ -112,6 +178,102 @@ pub fn commit<T: Serialize>(data: &T) {
     ENV.get().commit(data);
 }
 
+/// Returns an iterator over the command-line arguments the host gave the
+/// guest, like `std::env::args`.
+///
+/// # Panics
+///
+/// The iterator panics if an argument is not valid unicode.
+pub fn args() -> Args {
+    Args {
+        index: 0,
+        count: unsafe { sys_argc() },
+    }
+}
+
+/// An iterator over the guest's command-line arguments, returned by [args].
+pub struct Args {
+    index: usize,
+    count: usize,
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.index == self.count {
+            return None;
+        }
+        // Ask for the length first, then fetch the whole argument.
+        let len = unsafe { sys_argv(self.index, ptr::null_mut(), 0) };
+        let mut buf = vec![0; len];
+        unsafe { sys_argv(self.index, buf.as_mut_ptr(), len) };
+        self.index += 1;
+        Some(String::from_utf8(buf).expect("Argument is not valid unicode"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.count - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+/// Returns the value of an environment variable set by the host, like
+/// `std::env::var`.  Returns `None` if the variable is not set or its value
+/// is not valid unicode.
+pub fn var(name: &str) -> Option<String> {
+    let len = unsafe { sys_getenv(name.as_ptr(), name.len(), ptr::null_mut(), 0) };
+    if len == ENV_VAR_UNSET {
+        return None;
+    }
+    let mut buf = vec![0; len];
+    unsafe { sys_getenv(name.as_ptr(), name.len(), buf.as_mut_ptr(), len) };
+    String::from_utf8(buf).ok()
+}
+
+/// Returns a handle to the guest's standard input.
+pub fn stdin() -> Stdin {
+    Stdin(())
+}
+
+/// A handle to the guest's standard input, returned by [stdin].
+///
+/// Unlike [read], which receives all of its input from the host in a single
+/// exchange, the standard input is requested from the host in chunks as it is
+/// read, so it is not limited by the size of the input region.  With the
+/// `std` feature, this implements [std::io::Read].
+pub struct Stdin(());
+
+impl Stdin {
+    /// Reads up to `buf.len()` bytes into `buf`, returning the number of bytes
+    /// read.  The host may return fewer bytes than requested; a return of 0
+    /// means the input is exhausted.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        unsafe { sys_read(FILENO_STDIN, buf.as_mut_ptr(), buf.len()) }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(Stdin::read(self, buf))
+    }
+}
+
+/// Lets the host end the current segment here if it has run long enough, so
+/// that a long execution can be proven in several segments with
+/// `Prover::run_session`.  If the host pauses the guest, this returns in the
+/// next segment once the guest's state has been restored; otherwise it
+/// returns straight away.
+///
+/// Pausing and resuming each hash the guest's stack, heap, static data, input
+/// and journal, so checkpoints are best placed where the stack is shallow.
+pub fn checkpoint() {
+    segment::checkpoint()
+}
+
 /// Returns the number of processor cycles that have occured since the guest
 /// began.
 pub fn get_cycle_count() -> usize {
@@ -165,14 +327,45 @@ impl Env {
         send_recv(SENDRECV_CHANNEL_STDOUT, bytemuck::cast_slice(buf));
     }
 
-    fn finalize(&mut self, result: *mut usize) {
-        let len_words = self.commit_len;
+    fn finalize(&mut self, result: *mut usize, exit_code: ExitCode, post_state: Option<&Digest>) {
+        // The digests of the states the guest resumed from and paused in, if
+        // any, and the exit code are written to the outputs after the
+        // journal, so the seal proves them too.  A paused guest's journal is
+        // part of its state, so it is only committed when the guest halts.
+        let pre_state = segment::pre_state();
+        let mut trailer = [0; 2 * DIGEST_WORDS + ExitCode::WORDS];
+        let mut trailer_len = 0;
+        for digest in [pre_state, post_state].into_iter().flatten() {
+            trailer[trailer_len..trailer_len + DIGEST_WORDS].copy_from_slice(digest.get());
+            trailer_len += DIGEST_WORDS;
+        }
+        trailer[trailer_len..trailer_len + ExitCode::WORDS].copy_from_slice(&exit_code.to_words());
+        trailer_len += ExitCode::WORDS;
+        let journal_len = match post_state {
+            Some(_) => 0,
+            None => self.commit_len,
+        };
+        let start = self.commit_len - journal_len;
+        let len_words = journal_len + trailer_len;
+        assert!(
+            self.commit_len + trailer_len <= memory::COMMIT.len_words(),
+            "Journal is too large"
+        );
+        unsafe {
+            ptr::copy_nonoverlapping(
+                trailer.as_ptr(),
+                (memory::COMMIT.start() as *mut u32).add(self.commit_len),
+                trailer_len,
+            )
+        };
         let len_bytes = len_words * WORD_SIZE;
-        let slice: &[u32] =
-            unsafe { slice::from_raw_parts(memory::COMMIT.start() as _, len_words) };
+        let slice: &[u32] = unsafe {
+            slice::from_raw_parts((memory::COMMIT.start() as *const u32).add(start), len_words)
+        };
 
-        // Write the full data out to the host
-        unsafe { sys_commit(slice.as_ptr(), len_bytes) };
+        // Write the journal out to the host, which gets the exit code when the
+        // guest halts.
+        unsafe { sys_commit(slice.as_ptr(), journal_len * WORD_SIZE) };
 
         // If the total proof message is small (<= 32 bytes), return it directly
         // from the proof, otherwise SHA it and return the hash.
@@ -197,12 +390,17 @@ impl Env {
             );
         }
         unsafe {
-            result.add(8).write_volatile(len_bytes);
+            let flags = match pre_state {
+                Some(_) => EXIT_CODE_FLAG | RESUMED_FLAG,
+                None => EXIT_CODE_FLAG,
+            };
+            result.add(8).write_volatile(len_bytes | flags);
             memory_barrier(result);
             for i in 0..9 {
                 sys_output(i, (*result.add(i.try_into().unwrap())).try_into().unwrap());
             }
-            sys_halt()
+            let exit_words = exit_code.to_words();
+            sys_halt(exit_words[0], exit_words[1]);
         }
     }
 }
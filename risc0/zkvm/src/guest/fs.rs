@@ -0,0 +1,329 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read-only access to files supplied by the host, e.g. with
+//! `ProverOpts::with_fs_root`.
+//!
+//! Paths are relative to the root of the host's files, whether or not they
+//! start with a `/`.  The host records which bytes of each file the guest
+//! reads, so it can report exactly what the proof depended on.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use risc0_zkvm_platform::{
+    io::{fs::*, SENDRECV_CHANNEL_FS},
+    syscall::sys_read,
+};
+
+use crate::guest::env::send_recv;
+
+/// An error accessing a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// There is no file or directory at the path.
+    NotFound,
+    /// A directory was expected, but the path is a file.
+    NotADirectory,
+    /// A file was expected, but the path is a directory.
+    IsADirectory,
+    /// The path or the position to seek to is invalid.
+    InvalidInput,
+    /// The host failed to read the file or directory.
+    Io,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::NotFound => "No such file or directory",
+            Error::NotADirectory => "Not a directory",
+            Error::IsADirectory => "Is a directory",
+            Error::InvalidInput => "Invalid input",
+            Error::Io => "Input/output error",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        let kind = match err {
+            Error::NotFound => std::io::ErrorKind::NotFound,
+            Error::NotADirectory | Error::IsADirectory | Error::Io => std::io::ErrorKind::Other,
+            Error::InvalidInput => std::io::ErrorKind::InvalidInput,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+/// A result of a filesystem operation.
+pub type Result<T> = core::result::Result<T, Error>;
+
+// Sends a request to the host, returning the words of the reply after the
+// status.
+fn request(op: u32, args: &[u32], path: &str) -> Result<Reply> {
+    let mut buf = Vec::with_capacity(4 * (args.len() + 1) + path.len());
+    buf.extend_from_slice(&op.to_le_bytes());
+    for arg in args {
+        buf.extend_from_slice(&arg.to_le_bytes());
+    }
+    buf.extend_from_slice(path.as_bytes());
+    let mut reply = Reply(send_recv(SENDRECV_CHANNEL_FS, &buf));
+    match reply.u32() {
+        STATUS_OK => Ok(reply),
+        STATUS_NOT_FOUND => Err(Error::NotFound),
+        STATUS_NOT_A_DIRECTORY => Err(Error::NotADirectory),
+        STATUS_IS_A_DIRECTORY => Err(Error::IsADirectory),
+        STATUS_IO_ERROR => Err(Error::Io),
+        _ => Err(Error::InvalidInput),
+    }
+}
+
+struct Reply(&'static [u8]);
+
+impl Reply {
+    fn u32(&mut self) -> u32 {
+        let (word, rest) = self.0.split_at(4);
+        self.0 = rest;
+        u32::from_le_bytes(word.try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        let lo = self.u32() as u64;
+        let hi = self.u32() as u64;
+        hi << 32 | lo
+    }
+
+    fn string(&mut self, len: usize) -> String {
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = &rest[(4 - len % 4) % 4..];
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+/// Information about a file or directory, returned by [metadata].
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+    is_dir: bool,
+    len: u64,
+}
+
+impl Metadata {
+    /// Returns true if this is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Returns true if this is a file.
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Returns information about the file or directory at the given path.
+pub fn metadata(path: &str) -> Result<Metadata> {
+    let mut reply = request(OP_METADATA, &[], path)?;
+    Ok(Metadata {
+        is_dir: reply.u32() == KIND_DIR,
+        len: reply.u64(),
+    })
+}
+
+/// An entry in a directory, returned by [read_dir].
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    name: String,
+    is_dir: bool,
+}
+
+impl DirEntry {
+    /// Returns the name of the entry, without the directory's path.
+    pub fn file_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+}
+
+/// Returns the entries of the directory at the given path, sorted by name.
+pub fn read_dir(path: &str) -> Result<Vec<DirEntry>> {
+    let mut reply = request(OP_READ_DIR, &[], path)?;
+    let count = reply.u32();
+    Ok((0..count)
+        .map(|_| {
+            let is_dir = reply.u32() == KIND_DIR;
+            let len = reply.u32() as usize;
+            DirEntry {
+                name: reply.string(len),
+                is_dir,
+            }
+        })
+        .collect())
+}
+
+/// Reads the whole file at the given path.
+pub fn read(path: &str) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads the whole file at the given path, replacing any invalid unicode.
+pub fn read_to_string(path: &str) -> Result<String> {
+    Ok(String::from_utf8_lossy(&read(path)?).into_owned())
+}
+
+/// A position to seek to in a [File].
+#[derive(Clone, Copy, Debug)]
+pub enum SeekFrom {
+    /// The given number of bytes from the start of the file.
+    Start(u64),
+    /// The given number of bytes from the end of the file.
+    End(i64),
+    /// The given number of bytes from the current position.
+    Current(i64),
+}
+
+/// A file opened for reading.
+pub struct File {
+    fd: u32,
+    len: u64,
+    pos: u64,
+    // The position of the host's side of the file, which is only updated
+    // when the guest reads, to avoid a request for every seek.
+    host_pos: u64,
+}
+
+impl File {
+    /// Opens the file at the given path.
+    pub fn open(path: &str) -> Result<File> {
+        let mut reply = request(OP_OPEN, &[], path)?;
+        Ok(File {
+            fd: reply.u32(),
+            len: reply.u64(),
+            pos: 0,
+            host_pos: 0,
+        })
+    }
+
+    /// Returns information about the file.
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            is_dir: false,
+            len: self.len,
+        }
+    }
+
+    /// Reads up to `buf.len()` bytes into `buf`, returning the number of
+    /// bytes read.  Returns 0 at the end of the file.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.pos != self.host_pos {
+            request(
+                OP_SEEK,
+                &[self.fd, self.pos as u32, (self.pos >> 32) as u32],
+                "",
+            )?;
+            self.host_pos = self.pos;
+        }
+        let nread = unsafe { sys_read(self.fd, buf.as_mut_ptr(), buf.len()) };
+        self.pos += nread as u64;
+        self.host_pos = self.pos;
+        Ok(nread)
+    }
+
+    /// Reads the rest of the file, appending it to `buf`.  Returns the number
+    /// of bytes read.
+    pub fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let remaining = self.len.saturating_sub(self.pos) as usize;
+        buf.resize(start + remaining, 0);
+        let mut filled = start;
+        while filled < buf.len() {
+            let nread = File::read(self, &mut buf[filled..])?;
+            if nread == 0 {
+                break;
+            }
+            filled += nread;
+        }
+        buf.truncate(filled);
+        Ok(filled - start)
+    }
+
+    /// Moves the position the next read starts from, returning the new
+    /// position from the start of the file.
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.pos = offset;
+                return Ok(offset);
+            }
+            SeekFrom::End(offset) => (self.len, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        let pos = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        };
+        self.pos = pos.ok_or(Error::InvalidInput)?;
+        Ok(self.pos)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        // The host doesn't reply to a close.
+        let mut buf = [0; 8];
+        buf[..4].copy_from_slice(&OP_CLOSE.to_le_bytes());
+        buf[4..].copy_from_slice(&self.fd.to_le_bytes());
+        send_recv(SENDRECV_CHANNEL_FS, &buf);
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(File::read(self, buf)?)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Seek for File {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            std::io::SeekFrom::Start(offset) => SeekFrom::Start(offset),
+            std::io::SeekFrom::End(offset) => SeekFrom::End(offset),
+            std::io::SeekFrom::Current(offset) => SeekFrom::Current(offset),
+        };
+        Ok(File::seek(self, pos)?)
+    }
+}
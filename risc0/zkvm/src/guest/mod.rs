@@ -21,6 +21,11 @@
 //! [env::read] and commit public output data using [env::commit]; additional
 //! I/O functionality is also available in [mod@env].
 //!
+//! On the host, [mod@env] is replaced by an implementation backed by
+//! in-memory channels, so guest logic can be unit tested without the zkVM
+//! using [run_native](crate::testutil::run_native).  The rest of this module
+//! is only available in the guest.
+//!
 //! For example[^starter-ex], the following guest code proves a number is
 //! composite by multiplying two unsigned integers, and panicking if either is
 //! `1` or if the multiplication overflows:
@@ -53,11 +58,27 @@
 #![deny(missing_docs)]
 
 mod alloc;
+#[cfg(any(target_os = "zkvm", doc))]
+pub mod env;
+#[cfg(all(not(target_os = "zkvm"), not(doc)))]
+#[path = "native_env.rs"]
 pub mod env;
+#[cfg(any(target_os = "zkvm", doc))]
+pub mod fs;
+#[cfg(all(not(target_os = "zkvm"), doc))]
+mod native_env;
+#[cfg(target_os = "zkvm")]
+mod segment;
+#[cfg(any(target_os = "zkvm", doc))]
 pub mod sha;
 
 use core::{arch::asm, mem, ptr};
 
+#[cfg(all(not(target_os = "zkvm"), not(doc)))]
+pub(crate) use env::run as run_native;
+#[cfg(all(not(target_os = "zkvm"), doc))]
+pub(crate) use native_env::run as run_native;
+#[cfg(target_os = "zkvm")]
 use risc0_zkvm_platform::syscall::sys_panic;
 
 pub use crate::entry;
@@ -75,13 +96,30 @@ fn _fault() -> ! {
     unreachable!();
 }
 
+// Lets crates which depend on `getrandom` get random bytes from the host.
+#[cfg(target_os = "zkvm")]
+fn zkvm_getrandom(dest: &mut [u8]) -> Result<(), getrandom::Error> {
+    unsafe { risc0_zkvm_platform::syscall::sys_random(dest.as_mut_ptr(), dest.len()) };
+    Ok(())
+}
+
+#[cfg(target_os = "zkvm")]
+getrandom::register_custom_getrandom!(zkvm_getrandom);
+
 /// Aborts the guest with the given message.
 pub fn abort(msg: &str) -> ! {
-    // A compliant host should fault when it receives this syscall.
-    unsafe { sys_panic(msg.as_ptr(), msg.len()) };
+    #[cfg(target_os = "zkvm")]
+    {
+        // A compliant host should fault when it receives this syscall.
+        unsafe { sys_panic(msg.as_ptr(), msg.len()) };
+
+        // As a fallback for non-compliant hosts, issue an illegal instruction.
+        _fault()
+    }
 
-    // As a fallback for non-compliant hosts, issue an illegal instruction.
-    _fault()
+    // Running natively, there's no host to tell.
+    #[cfg(not(target_os = "zkvm"))]
+    panic!("{msg}")
 }
 
 #[cfg(all(not(feature = "std"), target_os = "zkvm"))]
@@ -91,7 +129,7 @@ mod handlers {
     #[panic_handler]
     fn panic_fault(panic_info: &PanicInfo) -> ! {
         let msg = ::alloc::format!("{}", panic_info);
-        crate::guest::abort(&msg)
+        crate::guest::env::fault(&msg)
     }
 
     #[alloc_error_handler]
@@ -137,7 +175,7 @@ unsafe extern "C" fn __start(result: *mut usize) {
     }
     __main();
 
-    env::finalize(result);
+    env::finalize(result, crate::ExitCode::Halted(0));
 }
 
 /// Align the given address `addr` upwards to alignment `align`.
@@ -157,6 +195,10 @@ _start:
     .option norelax;
     la gp, __global_pointer$;
     .option pop;
+    # Resumes a paused guest, on a stack below the one it saved, or returns
+    # to start the guest afresh.  The stack top must match RESUME_STACK_TOP.
+    li sp, 0x10000
+    jal ra, __resume
     la sp, __stack_init$;
     la a0, __result
     jal ra, __start
@@ -0,0 +1,221 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Functions for interacting with the host environment.
+//!
+//! This is the native implementation, used to unit test guest code on the
+//! host with [run_native](crate::testutil::run_native).  Instead of a zkVM,
+//! it talks to in-memory channels which are set up for the duration of each
+//! run; calling these functions outside of a run panics.
+
+use std::{cell::RefCell, panic};
+
+use risc0_zkvm_platform::io::{
+    SENDRECV_CHANNEL_INITIAL_INPUT, SENDRECV_CHANNEL_STDOUT, SENDRECV_CHANNEL_ZEROIO_INPUT,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    receipt::ExitCode,
+    serde::{to_vec, Deserializer},
+    testutil::NativeResult,
+};
+
+struct Env {
+    input: &'static [u32],
+    reader: Reader,
+    journal: Vec<u32>,
+    stdout: Vec<u8>,
+}
+
+thread_local! {
+    static ENV: RefCell<Option<Env>> = RefCell::new(None);
+}
+
+fn with_env<R>(f: impl FnOnce(&mut Env) -> R) -> R {
+    ENV.with(|env| {
+        f(env
+            .borrow_mut()
+            .as_mut()
+            .expect("The guest environment is only available within run_native"))
+    })
+}
+
+// The payload used to unwind out of the guest's main function when it exits.
+struct Exit(u32);
+
+pub(crate) fn run(main_fn: fn(), input: &[u32]) -> NativeResult {
+    // Data read by the guest may borrow from its input, which [read] and
+    // [send_recv] promise is 'static, so the input is leaked.
+    let input: &'static [u32] = Box::leak(input.to_vec().into_boxed_slice());
+    ENV.with(|env| {
+        let mut env = env.borrow_mut();
+        assert!(env.is_none(), "run_native can't be nested");
+        *env = Some(Env {
+            input,
+            reader: Reader(Deserializer::new(input)),
+            journal: Vec::new(),
+            stdout: Vec::new(),
+        });
+    });
+
+    let result = panic::catch_unwind(main_fn);
+    let env = ENV.with(|env| env.take()).unwrap();
+    let exit_code = match result {
+        Ok(()) => ExitCode::Halted(0),
+        Err(payload) => match payload.downcast::<Exit>() {
+            Ok(exit) => ExitCode::Halted(exit.0),
+            Err(payload) => panic::resume_unwind(payload),
+        },
+    };
+    NativeResult {
+        journal: env.journal,
+        stdout: env.stdout,
+        exit_code,
+    }
+}
+
+/// Reads and deserializes objects from a section of memory.
+pub struct Reader(Deserializer<'static>);
+
+impl Reader {
+    /// Read private data from the host.
+    pub fn read<T: Deserialize<'static>>(&mut self) -> T {
+        T::deserialize(&mut self.0).unwrap()
+    }
+}
+
+/// Halts the guest with the given exit code, committing it along with the
+/// journal.
+///
+/// Returning from the guest's main function is the same as exiting with 0.
+pub fn exit(exit_code: u32) -> ! {
+    panic::resume_unwind(Box::new(Exit(exit_code)))
+}
+
+/// Exchanges data with the host, returning the data from the host
+/// as a slice of bytes.
+///
+/// Only the initial input and stdout channels are available natively.
+pub fn send_recv(channel: u32, buf: &[u8]) -> &'static [u8] {
+    match channel {
+        SENDRECV_CHANNEL_INITIAL_INPUT => with_env(|env| bytemuck::cast_slice(env.input)),
+        SENDRECV_CHANNEL_STDOUT => {
+            with_env(|env| env.stdout.extend_from_slice(buf));
+            &[]
+        }
+        _ => panic!("Channel {channel} is not available when running natively"),
+    }
+}
+
+/// Exchanges data with the host, returning the data from the host as
+/// a slice of words and the length in bytes.
+pub fn send_recv_as_u32(channel: u32, buf: &[u8]) -> &'static [u32] {
+    bytemuck::cast_slice(send_recv(channel, buf))
+}
+
+/// Read private data from the host.
+pub fn read<T: Deserialize<'static>>() -> T {
+    with_env(|env| env.reader.read())
+}
+
+/// Read the next value the host added with `Prover::add_input_zeroio`.
+///
+/// There are no such values when running natively, so this panics.
+pub fn read_zeroio<T: risc0_zeroio::Deserialize<'static>>() -> T::RefType {
+    T::deserialize_from(send_recv_as_u32(SENDRECV_CHANNEL_ZEROIO_INPUT, &[]))
+}
+
+/// Write private data to the host.
+pub fn write<T: Serialize>(data: &T) {
+    let words = to_vec(data).unwrap();
+    with_env(|env| env.stdout.extend_from_slice(bytemuck::cast_slice(&words)));
+}
+
+/// Commit public data to the journal.
+pub fn commit<T: Serialize>(data: &T) {
+    let words = to_vec(data).unwrap();
+    with_env(|env| {
+        // Copy to stdout, as in the zkVM.
+        env.stdout.extend_from_slice(bytemuck::cast_slice(&words));
+        env.journal.extend_from_slice(&words);
+    });
+}
+
+/// Returns an iterator over the command-line arguments the host gave the
+/// guest, which are always empty when running natively.
+pub fn args() -> Args {
+    Args(Vec::new().into_iter())
+}
+
+/// An iterator over the guest's command-line arguments, returned by [args].
+pub struct Args(std::vec::IntoIter<String>);
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+/// Returns the value of an environment variable set by the host.  No
+/// variables are set when running natively, so this always returns `None`.
+pub fn var(_name: &str) -> Option<String> {
+    None
+}
+
+/// Returns a handle to the guest's standard input.
+pub fn stdin() -> Stdin {
+    Stdin(())
+}
+
+/// A handle to the guest's standard input, returned by [stdin].  It is
+/// always empty when running natively.
+pub struct Stdin(());
+
+impl Stdin {
+    /// Reads up to `buf.len()` bytes into `buf`, returning the number of bytes
+    /// read.  A return of 0 means the input is exhausted.
+    pub fn read(&mut self, _buf: &mut [u8]) -> usize {
+        0
+    }
+}
+
+impl std::io::Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(Stdin::read(self, buf))
+    }
+}
+
+/// Lets the host end the current segment here, which never happens when
+/// running natively.
+pub fn checkpoint() {}
+
+/// Returns the number of processor cycles that have occured since the guest
+/// began, which is always 0 when running natively.
+pub fn get_cycle_count() -> usize {
+    0
+}
+
+/// Print a message to the debug console.
+pub fn log(msg: &str) {
+    eprintln!("{msg}");
+}
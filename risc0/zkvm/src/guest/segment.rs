@@ -0,0 +1,214 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pausing the guest at the end of a segment, and resuming it in the next.
+//!
+//! A pausing guest saves its callee-saved registers, hashes the memory which
+//! holds its state, and halts with [ExitCode::Paused](crate::ExitCode),
+//! committing the digest of its state.  The next segment runs the same
+//! program from its entry point, where the host writes the saved memory back
+//! before the guest touches it.  The guest hashes it again, commits that
+//! digest along with the rest of its outputs, and carries on from where it
+//! paused.  A [SessionReceipt](crate::receipt::SessionReceipt) checks that
+//! each segment starts from the state the one before it paused in.
+
+use core::{mem::MaybeUninit, ptr, slice};
+
+use risc0_zkp::core::sha::{Digest, SHA256_INIT};
+use risc0_zkvm_platform::{
+    abi, memory,
+    syscall::{input_end, sys_pause, sys_pause_requested, sys_resume},
+    WORD_SIZE,
+};
+
+use crate::guest::{abort, align_up, env, sha};
+
+// The number of memory regions which hold the guest's state.
+const REGIONS: usize = 5;
+
+// The layout of the guest's state is a start address and length in bytes for
+// each region, padded to a whole SHA block.
+const LAYOUT_WORDS: usize = 16;
+
+// Regions are hashed a whole number of SHA blocks at a time, so hashing
+// them never needs padding, which would allocate.
+const BLOCK_BYTES: usize = 64;
+
+// A guest is resumed on a stack at the bottom of the stack region, below the
+// stack it saved.  This must match the `_start` routine.
+const RESUME_STACK_TOP: usize = memory::STACK.start() + 0x10000;
+
+// The registers which are preserved across a call: ra, sp, gp, tp and s0 to
+// s11, in that order.
+#[repr(C)]
+struct Context {
+    regs: [u32; 16],
+}
+
+const CONTEXT_SP: usize = 1;
+
+// The registers saved by the last pause.  Like the rest of the guest's static
+// data, this is part of the state restored when the guest is resumed.
+static mut CONTEXT: Context = Context { regs: [0; 16] };
+
+// The digest of the state this segment resumed from, if it did.
+static mut PRE_STATE: Option<Digest> = None;
+
+extern "C" {
+    // Saves the callee-saved registers to the context, and returns 0.
+    fn __risc0_save_context(context: *mut Context) -> u32;
+
+    // Restores the callee-saved registers from the context, and returns 1
+    // from the call to __risc0_save_context which saved them.
+    fn __risc0_restore_context(context: *const Context) -> !;
+}
+
+core::arch::global_asm!(
+    r#"
+.section .text.__risc0_save_context;
+.globl __risc0_save_context;
+__risc0_save_context:
+    sw ra, 0(a0)
+    sw sp, 4(a0)
+    sw gp, 8(a0)
+    sw tp, 12(a0)
+    sw s0, 16(a0)
+    sw s1, 20(a0)
+    sw s2, 24(a0)
+    sw s3, 28(a0)
+    sw s4, 32(a0)
+    sw s5, 36(a0)
+    sw s6, 40(a0)
+    sw s7, 44(a0)
+    sw s8, 48(a0)
+    sw s9, 52(a0)
+    sw s10, 56(a0)
+    sw s11, 60(a0)
+    li a0, 0
+    ret
+
+.section .text.__risc0_restore_context;
+.globl __risc0_restore_context;
+__risc0_restore_context:
+    lw ra, 0(a0)
+    lw sp, 4(a0)
+    lw gp, 8(a0)
+    lw tp, 12(a0)
+    lw s0, 16(a0)
+    lw s1, 20(a0)
+    lw s2, 24(a0)
+    lw s3, 28(a0)
+    lw s4, 32(a0)
+    lw s5, 36(a0)
+    lw s6, 40(a0)
+    lw s7, 44(a0)
+    lw s8, 48(a0)
+    lw s9, 52(a0)
+    lw s10, 56(a0)
+    lw s11, 60(a0)
+    li a0, 1
+    ret
+"#
+);
+
+/// Returns the digest of the state this segment resumed from, if it did.
+pub(crate) fn pre_state() -> Option<&'static Digest> {
+    unsafe { PRE_STATE.as_ref() }
+}
+
+/// Pauses the guest if the host asks it to.
+pub(crate) fn checkpoint() {
+    if unsafe { sys_pause_requested() } {
+        unsafe { pause() }
+    }
+}
+
+// Saves the guest's registers and pauses it.  Returns once a later segment
+// has resumed the guest.
+#[inline(never)]
+unsafe fn pause() {
+    if __risc0_save_context(ptr::addr_of_mut!(CONTEXT)) == 0 {
+        finish_pause()
+    }
+}
+
+// Hashes the guest's state, hands it to the host and halts.  This runs below
+// the stack pointer saved in the context, so it doesn't change the part of
+// the stack it hashes.
+#[inline(never)]
+unsafe fn finish_pause() -> ! {
+    let sp = CONTEXT.regs[CONTEXT_SP] as usize;
+    if sp < RESUME_STACK_TOP {
+        abort("Stack is too deep to pause the guest")
+    }
+    let layout = layout(sp);
+    let digest = hash_state(&layout);
+    sys_pause(layout.as_ptr(), LAYOUT_WORDS, digest.get());
+    env::pause(&digest)
+}
+
+// Called by `_start` on the resume stack, before the guest touches any other
+// memory.  Returns if this segment starts the guest afresh; otherwise the
+// host has restored the guest's memory, and this restores its registers and
+// returns to where it paused.
+#[no_mangle]
+unsafe extern "C" fn __resume() {
+    // The host writes the layout here, so it mustn't be initialized first.
+    let mut layout = MaybeUninit::<[u32; LAYOUT_WORDS]>::uninit();
+    if !sys_resume(layout.as_mut_ptr().cast(), LAYOUT_WORDS) {
+        return;
+    }
+    let digest = hash_state(layout.assume_init_ref());
+    PRE_STATE = Some(digest);
+    __risc0_restore_context(ptr::addr_of!(CONTEXT))
+}
+
+// Returns the regions of memory which hold the state of a guest with the
+// given stack pointer: its stack, static data, heap, the input it has been
+// given and its journal.
+fn layout(sp: usize) -> [u32; LAYOUT_WORDS] {
+    extern "C" {
+        static __bss_end: u8;
+    }
+    let data_end = unsafe { ptr::addr_of!(__bss_end) } as usize;
+    let regions: [(usize, usize); REGIONS] = [
+        (sp, memory::STACK.end()),
+        (memory::DATA.start(), data_end),
+        (memory::HEAP.start(), abi::zkvm_abi_alloc_words(0) as usize),
+        (memory::INPUT.start(), input_end()),
+        (
+            memory::COMMIT.start(),
+            memory::COMMIT.start() + env::commit_len() * WORD_SIZE,
+        ),
+    ];
+    let mut layout = [0; LAYOUT_WORDS];
+    for (i, (start, end)) in regions.into_iter().enumerate() {
+        layout[2 * i] = start as u32;
+        layout[2 * i + 1] = align_up(end - start, BLOCK_BYTES) as u32;
+    }
+    layout
+}
+
+// Hashes the layout followed by the regions of memory it describes.
+unsafe fn hash_state(layout: &[u32; LAYOUT_WORDS]) -> Digest {
+    let mut digest = Digest::default();
+    let out: *mut Digest = &mut digest;
+    sha::update_u32(out, &SHA256_INIT, layout, sha::WithoutTrailer);
+    for region in layout[..2 * REGIONS].chunks_exact(2) {
+        let len_words = region[1] as usize / WORD_SIZE;
+        let words = slice::from_raw_parts(region[0] as *const u32, len_words);
+        sha::update_u32(out, out, words, sha::WithoutTrailer);
+    }
+    digest
+}
@@ -13,14 +13,28 @@
 // limitations under the License.
 
 //! Functions for computing SHA-256 hashes.
+//!
+//! Besides the one-shot functions here and in [Impl], [Sha256] hashes its
+//! input incrementally.  It implements the RustCrypto [digest::Digest]
+//! trait, so crates built on that trait (e.g. for HMAC or HKDF) can use the
+//! accelerated SHA-256 compression in the guest.
 
 use alloc::format;
 use alloc::{boxed::Box, vec::Vec};
 use core::{
     cell::UnsafeCell,
+    fmt,
     mem::{self, MaybeUninit},
 };
 
+use digest::{
+    core_api::{
+        AlgorithmName, Block as DigestBlock, BlockSizeUser, Buffer, BufferKindUser, CoreWrapper,
+        Eager, FixedOutputCore, OutputSizeUser, UpdateCore,
+    },
+    typenum::{U32, U64},
+    HashMarker, Output, Reset,
+};
 use risc0_zkp::core::sha::{Digest, DIGEST_WORDS, SHA256_INIT};
 use risc0_zkvm_platform::{memory, syscall::sys_sha_compress, WORD_SIZE};
 use serde::Serialize;
@@ -258,3 +272,102 @@ impl risc0_zkp::core::sha::Sha for Impl {
         unsafe { &*digest }
     }
 }
+
+/// A SHA-256 hasher which takes its input incrementally.
+///
+/// Input is buffered until a full 64 byte block is available, which is then
+/// compressed using the guest's SHA-256 acceleration.  Use it through the
+/// [digest::Digest] trait:
+///
+/// ```ignore
+/// use digest::Digest;
+/// use risc0_zkvm::guest::sha::Sha256;
+///
+/// let mut hasher = Sha256::new();
+/// hasher.update(b"hello ");
+/// hasher.update(b"world");
+/// let hash: [u8; 32] = hasher.finalize().into();
+/// ```
+pub type Sha256 = CoreWrapper<Sha256Core>;
+
+/// The state of a [Sha256] hasher between full blocks.
+#[derive(Clone)]
+pub struct Sha256Core {
+    state: Digest,
+    block_count: u64,
+}
+
+impl Sha256Core {
+    fn compress_block(&mut self, block: &DigestBlock<Self>) {
+        let state: *mut Digest = &mut self.state;
+        match bytemuck::try_from_bytes::<Block>(block.as_slice()) {
+            Ok(block) => compress(state, state, &block[0], &block[1]),
+            Err(_) => {
+                // The acceleration needs the block to be word aligned.
+                let mut aligned: Block = Default::default();
+                bytemuck::bytes_of_mut(&mut aligned).copy_from_slice(block.as_slice());
+                compress(state, state, &aligned[0], &aligned[1]);
+            }
+        }
+    }
+}
+
+impl Default for Sha256Core {
+    fn default() -> Self {
+        Sha256Core {
+            state: SHA256_INIT,
+            block_count: 0,
+        }
+    }
+}
+
+impl HashMarker for Sha256Core {}
+
+impl BlockSizeUser for Sha256Core {
+    type BlockSize = U64;
+}
+
+impl BufferKindUser for Sha256Core {
+    type BufferKind = Eager;
+}
+
+impl OutputSizeUser for Sha256Core {
+    type OutputSize = U32;
+}
+
+impl UpdateCore for Sha256Core {
+    fn update_blocks(&mut self, blocks: &[DigestBlock<Self>]) {
+        for block in blocks {
+            self.compress_block(block);
+        }
+        self.block_count += blocks.len() as u64;
+    }
+}
+
+impl FixedOutputCore for Sha256Core {
+    fn finalize_fixed_core(&mut self, buffer: &mut Buffer<Self>, out: &mut Output<Self>) {
+        let total_bits = (self.block_count * 64 + buffer.get_pos() as u64) * 8;
+        buffer.len64_padding_be(total_bits, |block| self.compress_block(block));
+        for (chunk, word) in out.chunks_exact_mut(WORD_SIZE).zip(self.state.get()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+    }
+}
+
+impl Reset for Sha256Core {
+    fn reset(&mut self) {
+        *self = Default::default();
+    }
+}
+
+impl AlgorithmName for Sha256Core {
+    fn write_alg_name(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sha256")
+    }
+}
+
+impl fmt::Debug for Sha256Core {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sha256Core { ... }")
+    }
+}
@@ -37,7 +37,7 @@
 
 extern crate alloc;
 
-#[cfg(any(target_os = "zkvm", doc))]
+#[cfg(any(target_os = "zkvm", doc, feature = "std"))]
 pub mod guest;
 pub mod method_id;
 #[cfg(not(target_os = "zkvm"))]
@@ -47,14 +47,22 @@ pub mod serde;
 pub mod sha;
 #[cfg(test)]
 mod tests;
+#[cfg(all(not(target_os = "zkvm"), feature = "std"))]
+pub mod testutil;
 
 pub use anyhow::Result;
 
 #[cfg(not(target_os = "zkvm"))]
-pub use crate::prove::{Prover, ProverOpts};
+pub use crate::prove::trace;
+#[cfg(not(target_os = "zkvm"))]
+pub use crate::prove::{
+    execute, ExecuteResult, ExecutionError, HostChannel, MemoryFaultKind, MemoryStats, ProveEvent,
+    ProveHandle, ProvePhase, Prover, ProverOpts, ProverPool, Snapshot, SyscallContext,
+    SyscallHandler,
+};
 pub use crate::{
     method_id::{MethodId, DEFAULT_METHOD_ID_LIMIT},
-    receipt::Receipt,
+    receipt::{ExitCode, Receipt, SegmentReceipt, SessionReceipt},
 };
 
 const CIRCUIT: risc0_circuit_rv32im::CircuitImpl = risc0_circuit_rv32im::CircuitImpl::new();
@@ -0,0 +1,132 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for measuring line coverage of the guest.
+//!
+//! This records each program counter executed by the guest, and resolves
+//! them to source lines using the debug information in the ELF.  The
+//! results can be written in the lcov `.info` format used by tools such as
+//! `genhtml`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use addr2line::{object::read::File, Context};
+use anyhow::Result;
+use gimli::{EndianRcSlice, RunTimeEndian};
+
+use super::TraceEvent;
+
+/// Collects line coverage for the guest.
+pub struct Coverage {
+    ctx: Context<EndianRcSlice<RunTimeEndian>>,
+
+    // Every line with code, from the debug information
+    lines: BTreeMap<String, BTreeMap<u32, usize>>,
+
+    // Number of times each program counter was executed
+    hits: HashMap<u32, usize>,
+
+    // Cycle of the last instruction seen, so repeated events for the same
+    // instruction are only counted once
+    cycle: Option<u32>,
+}
+
+impl Coverage {
+    /// Return a new coverage collector for the given RISCV ELF.
+    pub fn new(elf_data: &[u8]) -> Result<Self> {
+        let file = File::parse(elf_data)?;
+        let ctx = Context::new(&file)?;
+
+        // Find all the lines which have code, so unexecuted lines show up as
+        // uncovered.
+        let mut lines: BTreeMap<String, BTreeMap<u32, usize>> = BTreeMap::new();
+        for (_addr, _len, loc) in ctx.find_location_range(0, u32::MAX as u64)? {
+            if let (Some(file), Some(line)) = (loc.file, loc.line) {
+                if line != 0 {
+                    lines.entry(file.to_string()).or_default().insert(line, 0);
+                }
+            }
+        }
+
+        Ok(Coverage {
+            ctx,
+            lines,
+            hits: HashMap::new(),
+            cycle: None,
+        })
+    }
+
+    /// Returns a callback to populate this coverage collector, suitable for
+    /// passing to ProverOpts::with_trace_callback.
+    pub fn make_trace_callback<'a>(
+        &'a mut self,
+    ) -> impl FnMut(TraceEvent) -> anyhow::Result<()> + 'a {
+        |event| {
+            if let TraceEvent::InstructionStart { cycle, pc } = event {
+                if self.cycle != Some(cycle) {
+                    *self.hits.entry(pc).or_insert(0) += 1;
+                    self.cycle = Some(cycle);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Returns the number of times each line was executed, indexed by
+    /// filename and line number.
+    pub fn line_hits(&self) -> BTreeMap<String, BTreeMap<u32, usize>> {
+        let mut lines = self.lines.clone();
+        for (pc, count) in self.hits.iter() {
+            let loc = match self.ctx.find_location(*pc as u64) {
+                Ok(Some(loc)) => loc,
+                Ok(None) => continue,
+                Err(err) => {
+                    eprintln!("Error finding location!  {:?}", err);
+                    continue;
+                }
+            };
+            if let (Some(file), Some(line)) = (loc.file, loc.line) {
+                if line != 0 {
+                    *lines
+                        .entry(file.to_string())
+                        .or_default()
+                        .entry(line)
+                        .or_insert(0) += count;
+                }
+            }
+        }
+        lines
+    }
+
+    /// Returns the coverage of this run in the lcov `.info` format, ready for
+    /// writing to a file.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (file, lines) in self.line_hits() {
+            writeln!(out, "TN:").unwrap();
+            writeln!(out, "SF:{file}").unwrap();
+            for (line, count) in lines.iter() {
+                writeln!(out, "DA:{line},{count}").unwrap();
+            }
+            writeln!(out, "LF:{}", lines.len()).unwrap();
+            let hit = lines.values().filter(|count| **count > 0).count();
+            writeln!(out, "LH:{hit}").unwrap();
+            writeln!(out, "end_of_record").unwrap();
+        }
+        out
+    }
+}
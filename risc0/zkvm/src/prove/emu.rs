@@ -0,0 +1,819 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A native RV32IM interpreter used for execute-only runs.
+//!
+//! Unlike [super::exec::RV32Executor], this does not step the circuit or
+//! record a trace; it only models the architectural state of the guest so that
+//! guest logic can be iterated on quickly.  Cycle counts are estimated from
+//! the cost of each instruction in the circuit.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Result};
+use log::{debug, trace};
+use risc0_zkp::{
+    adapter::PolyExt,
+    core::{
+        log2_ceil,
+        sha::{Digest, Sha, DIGEST_WORDS},
+    },
+    field::{
+        baby_bear::{BabyBearElem, BabyBearExtElem},
+        Elem,
+    },
+    MAX_CYCLES_PO2, MIN_PO2, ZK_CYCLES,
+};
+use risc0_zkvm_platform::{
+    memory::MEM_SIZE,
+    syscall::{
+        ecall,
+        nr::{
+            SYS_ARGC, SYS_ARGV, SYS_COMMIT, SYS_COMPUTE_POLY, SYS_CYCLE_COUNT, SYS_GETENV, SYS_IO,
+            SYS_LOG, SYS_PANIC, SYS_PAUSE, SYS_PAUSE_REQUESTED, SYS_RANDOM, SYS_READ, SYS_RESUME,
+        },
+        reg_abi::{REG_A0, REG_A1, REG_A2, REG_A3, REG_A4, REG_A7, REG_T0},
+    },
+    WORD_SIZE,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    elf::Program,
+    error::{ExecutionError, MemoryFaultKind},
+    exec::{
+        check_input_region, check_store, fault, region_end, CycleLimits, HostHandler, SavedState,
+    },
+    fs::FsState,
+    loader::Loader,
+    stats::{MemoryStats, MemoryTracker, TrackerState},
+    syscall::{get_register, SyscallContext},
+};
+use crate::{receipt::ExitCode, sha::sha, CIRCUIT};
+
+// Estimated number of circuit cycles used by the various kinds of
+// instructions.  The circuit computes right shifts with its divider, so they
+// take as long as divisions.
+const INSN_CYCLES: usize = 1;
+const DIV_CYCLES: usize = 2;
+const ECALL_CYCLES: usize = 2;
+const SHA_CYCLES: usize = 72;
+
+const MEM_WORDS: usize = MEM_SIZE / WORD_SIZE;
+
+// Number of words in each page of memory saved in a snapshot.  Pages which
+// are all zeros are omitted.
+const PAGE_WORDS: usize = 256;
+
+/// A snapshot of a guest paused in execute-only mode.
+///
+/// Snapshots may be persisted using [crate::serde] and resumed later with
+/// [super::Prover::resume_from], using a [super::Prover] built from the same
+/// ELF.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    pc: u32,
+    regs: Vec<u32>,
+    pages: BTreeMap<u32, Vec<u32>>,
+    cycles: usize,
+    tracker: TrackerState,
+    pub(crate) journal: Vec<u32>,
+    pub(crate) stdout: Vec<u8>,
+    // How far the guest got through the streams the host gives it, so that
+    // the same streams can be picked up from there on resuming.
+    pub(crate) stdin_pos: u64,
+    pub(crate) zeroio_pos: usize,
+    pub(crate) fs: FsState,
+}
+
+impl Snapshot {
+    /// The current version of the snapshot format.
+    ///
+    /// Bump this whenever the contents of a snapshot change.
+    pub const VERSION: u32 = 2;
+
+    /// The program counter of the next instruction to execute.
+    pub fn pc(&self) -> u32 {
+        self.pc
+    }
+
+    /// The number of guest cycles executed before the snapshot was taken.
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+}
+
+// Guest memory, indexed by word address.
+struct Memory {
+    words: Vec<u32>,
+    tracker: MemoryTracker,
+}
+
+impl Memory {
+    fn new(words: Vec<u32>, elf: &Program) -> Self {
+        // The circuit touches the whole image while loading it.
+        let tracker = MemoryTracker::new();
+        for addr in elf.image.keys() {
+            tracker.touch(*addr);
+        }
+        Self { words, tracker }
+    }
+
+    fn load_u32(&self, addr: u32) -> Result<u32> {
+        if addr % WORD_SIZE as u32 != 0 {
+            return Err(fault(addr, MemoryFaultKind::UnalignedLoad));
+        }
+        self.tracker.touch(addr);
+        self.words
+            .get(addr as usize / WORD_SIZE)
+            .copied()
+            .ok_or_else(|| fault(addr, MemoryFaultKind::OutOfRange))
+    }
+
+    fn store_u32(&mut self, addr: u32, value: u32) -> Result<()> {
+        if addr % WORD_SIZE as u32 != 0 {
+            return Err(fault(addr, MemoryFaultKind::UnalignedStore));
+        }
+        let word = self
+            .words
+            .get_mut(addr as usize / WORD_SIZE)
+            .ok_or_else(|| fault(addr, MemoryFaultKind::OutOfRange))?;
+        *word = value;
+        self.tracker.write(addr);
+        Ok(())
+    }
+
+    fn load_u8(&self, addr: u32) -> Result<u8> {
+        let word = self.load_u32(addr & !(WORD_SIZE as u32 - 1))?;
+        Ok((word >> ((addr % WORD_SIZE as u32) * 8)) as u8)
+    }
+
+    fn store_u8(&mut self, addr: u32, value: u8) -> Result<()> {
+        let aligned = addr & !(WORD_SIZE as u32 - 1);
+        let shift = (addr % WORD_SIZE as u32) * 8;
+        let word = self.load_u32(aligned)? & !(0xff << shift);
+        self.store_u32(aligned, word | (value as u32) << shift)
+    }
+
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>> {
+        (addr..region_end(addr, size)?)
+            .map(|addr| self.load_u8(addr))
+            .collect()
+    }
+
+    fn load_region_u32(&self, addr: u32, size: u32) -> Result<Vec<u32>> {
+        (addr..region_end(addr, size)?)
+            .step_by(WORD_SIZE)
+            .map(|addr| self.load_u32(addr))
+            .collect()
+    }
+
+    fn store_region(&mut self, addr: u32, slice: &[u8]) -> Result<()> {
+        trace!("store_region: 0x{addr:08X} <= {} bytes", slice.len());
+        check_store(addr, slice.len())?;
+        for (i, byte) in slice.iter().enumerate() {
+            self.store_u8(addr + i as u32, *byte)?;
+        }
+        Ok(())
+    }
+
+    fn store_region_u32(&mut self, addr: u32, slice: &[u32]) -> Result<()> {
+        check_store(addr, slice.len() * WORD_SIZE)?;
+        for (i, word) in slice.iter().enumerate() {
+            self.store_u32(addr + (i * WORD_SIZE) as u32, *word)?;
+        }
+        Ok(())
+    }
+
+    // Reads a SliceDescriptor at the given address and returns its contents.
+    fn read_slice<T: bytemuck::Pod>(&self, desc_addr: u32) -> Result<Vec<T>> {
+        let size = self.load_u32(desc_addr)?;
+        let addr = self.load_u32(desc_addr + WORD_SIZE as u32)?;
+        let elt_size = core::mem::size_of::<T>();
+        if size as usize % elt_size != 0 {
+            bail!("slice does not end on a boundary of T; size={size}, elt_size={elt_size}");
+        }
+        let words = self.load_region_u32(addr, size)?;
+        Ok(bytemuck::cast_slice(words.as_slice()).to_vec())
+    }
+
+    fn read_value<T: bytemuck::Pod>(&self, addr: u32) -> Result<T> {
+        let words = self.load_region_u32(addr, core::mem::size_of::<T>() as u32)?;
+        Ok(*bytemuck::from_bytes(bytemuck::cast_slice(
+            words.as_slice(),
+        )))
+    }
+
+    fn load_u16(&self, addr: u32) -> Result<u16> {
+        if addr % 2 != 0 {
+            return Err(fault(addr, MemoryFaultKind::UnalignedLoad));
+        }
+        Ok(self.load_u8(addr)? as u16 | (self.load_u8(addr + 1)? as u16) << 8)
+    }
+
+    fn store_u16(&mut self, addr: u32, value: u16) -> Result<()> {
+        if addr % 2 != 0 {
+            return Err(fault(addr, MemoryFaultKind::UnalignedStore));
+        }
+        self.store_u8(addr, value as u8)?;
+        self.store_u8(addr + 1, (value >> 8) as u8)
+    }
+}
+
+// Gives syscall handlers access to the guest state held by an Emulator.
+struct EmulatorSyscallContext<'a> {
+    regs: &'a [u32; 32],
+    memory: &'a mut Memory,
+    cycle: usize,
+}
+
+impl<'a> SyscallContext for EmulatorSyscallContext<'a> {
+    fn load_register(&self, reg: usize) -> Result<u32> {
+        get_register(self.regs, reg)
+    }
+
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>> {
+        self.memory.load_region(addr, size)
+    }
+
+    fn store_region(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        self.memory.store_region(addr, data)
+    }
+
+    fn cycle(&self) -> usize {
+        self.cycle
+    }
+}
+
+/// Executes guest code directly, without generating a circuit trace.
+pub struct Emulator<'a, H: HostHandler> {
+    handler: &'a mut H,
+    regs: [u32; 32],
+    pc: u32,
+    memory: Memory,
+    body_cycles: usize,
+    load_cycles: usize,
+    halted: bool,
+    limits: CycleLimits,
+}
+
+impl<'a, H: HostHandler> Emulator<'a, H> {
+    pub fn new(elf: &Program, handler: &'a mut H) -> Result<Self> {
+        let mut memory = vec![0; MEM_WORDS];
+        for (addr, word) in elf.image.iter() {
+            *memory
+                .get_mut(*addr as usize / WORD_SIZE)
+                .ok_or_else(|| anyhow!("ELF segment out of range: 0x{addr:08X}"))? = *word;
+        }
+        Ok(Self {
+            handler,
+            regs: [0; 32],
+            pc: elf.entry,
+            memory: Memory::new(memory, elf),
+            body_cycles: 0,
+            load_cycles: Loader::new(&elf.image).compute_min_cycles(),
+            halted: false,
+            limits: CycleLimits {
+                max_po2: MAX_CYCLES_PO2,
+                max_cycles: None,
+            },
+        })
+    }
+
+    /// Restores the state of a guest saved with [Emulator::snapshot].
+    pub fn resume(elf: &Program, snapshot: &Snapshot, handler: &'a mut H) -> Result<Self> {
+        if snapshot.version != Snapshot::VERSION {
+            bail!("Unsupported snapshot version: {}", snapshot.version);
+        }
+        let mut memory = vec![0; MEM_WORDS];
+        for (page, words) in snapshot.pages.iter() {
+            let start = *page as usize * PAGE_WORDS;
+            memory
+                .get_mut(start..start + words.len())
+                .ok_or_else(|| anyhow!("Snapshot page out of range: {page}"))?
+                .copy_from_slice(words);
+        }
+        Ok(Self {
+            handler,
+            regs: snapshot
+                .regs
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow!("Invalid register count in snapshot"))?,
+            pc: snapshot.pc,
+            memory: Memory {
+                words: memory,
+                tracker: MemoryTracker::restore(&snapshot.tracker),
+            },
+            body_cycles: snapshot.cycles,
+            load_cycles: Loader::new(&elf.image).compute_min_cycles(),
+            halted: false,
+            limits: CycleLimits {
+                max_po2: MAX_CYCLES_PO2,
+                max_cycles: None,
+            },
+        })
+    }
+
+    /// Stops execution with an error once the guest goes past the given
+    /// limits.
+    pub fn with_limits(self, limits: CycleLimits) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// Saves the architectural state of the guest.  The journal, stdout and
+    /// stream positions are left empty, since they belong to the host.
+    pub fn snapshot(&self) -> Snapshot {
+        let pages = self
+            .memory
+            .words
+            .chunks(PAGE_WORDS)
+            .enumerate()
+            .filter(|(_, words)| words.iter().any(|word| *word != 0))
+            .map(|(page, words)| (page as u32, words.to_vec()))
+            .collect();
+        Snapshot {
+            version: Snapshot::VERSION,
+            pc: self.pc,
+            regs: self.regs.to_vec(),
+            pages,
+            cycles: self.body_cycles,
+            tracker: self.memory.tracker.save(),
+            journal: Vec::new(),
+            stdout: Vec::new(),
+            stdin_pos: 0,
+            zeroio_pos: 0,
+            fs: FsState::default(),
+        }
+    }
+
+    /// Run until the guest halts, returning the estimated total number of
+    /// cycles the circuit would need to execute the same program.
+    #[tracing::instrument(skip_all)]
+    pub fn run(&mut self) -> Result<usize> {
+        self.run_until(usize::MAX)?;
+        debug!("HALT: {} body cycles", self.body_cycles);
+        Ok(self.cycles())
+    }
+
+    /// Run until the guest halts or the estimated total number of cycles
+    /// reaches `cycle_limit`.  Returns true if the guest halted.
+    pub fn run_until(&mut self, cycle_limit: usize) -> Result<bool> {
+        while !self.halted && self.cycles() < cycle_limit {
+            self.step()?;
+            self.limits.check(self.cycle(), self.po2())?;
+        }
+        Ok(self.halted)
+    }
+
+    /// The estimated number of cycles, including the cycles needed to load the
+    /// program and finalize the execution.
+    pub fn cycles(&self) -> usize {
+        self.load_cycles + self.body_cycles
+    }
+
+    // The estimated cycle at which the circuit would start the next
+    // instruction, as reported to the guest by SYS_CYCLE_COUNT.
+    fn cycle(&self) -> usize {
+        self.cycles() - Loader::FINI_CYCLES - ZK_CYCLES
+    }
+
+    /// The estimated size of the execution trace, as a power of 2.
+    pub fn po2(&self) -> usize {
+        core::cmp::max(log2_ceil(self.cycles() + 1), MIN_PO2)
+    }
+
+    /// Returns statistics on the guest's use of memory.
+    pub fn memory_stats(&self) -> MemoryStats {
+        self.memory.tracker.stats()
+    }
+
+    fn set_reg(&mut self, reg: usize, value: u32) {
+        if reg != 0 {
+            self.regs[reg] = value;
+            self.memory.tracker.set_register(reg, value);
+        }
+    }
+
+    fn step(&mut self) -> Result<()> {
+        let insn = self.memory.load_u32(self.pc)?;
+        let opcode = insn & 0x0000007f;
+        let rd = ((insn & 0x00000f80) >> 7) as usize;
+        let funct3 = (insn & 0x00007000) >> 12;
+        let rs1 = ((insn & 0x000f8000) >> 15) as usize;
+        let rs2 = ((insn & 0x01f00000) >> 20) as usize;
+        let funct7 = (insn & 0xfe000000) >> 25;
+
+        let imm_i = ((insn as i32) >> 20) as u32;
+        let imm_s = (((insn as i32) >> 25) << 5) as u32 | (insn >> 7) & 0x1f;
+        let imm_b = (((insn as i32) >> 31) << 12) as u32
+            | ((insn >> 7) & 0x1) << 11
+            | ((insn >> 25) & 0x3f) << 5
+            | ((insn >> 8) & 0xf) << 1;
+        let imm_u = insn & 0xfffff000;
+        let imm_j = (((insn as i32) >> 31) << 20) as u32
+            | insn & 0x000ff000
+            | ((insn >> 20) & 0x1) << 11
+            | ((insn >> 21) & 0x3ff) << 1;
+
+        let x1 = self.regs[rs1];
+        let x2 = self.regs[rs2];
+        let mut next_pc = self.pc.wrapping_add(WORD_SIZE as u32);
+        let mut cycles = INSN_CYCLES;
+
+        let pc = self.pc;
+        let illegal = || anyhow::Error::from(ExecutionError::IllegalInstruction { word: insn, pc });
+
+        match opcode {
+            0b0000011 => {
+                let addr = x1.wrapping_add(imm_i);
+                let value = match funct3 {
+                    0x0 => self.memory.load_u8(addr)? as i8 as i32 as u32,
+                    0x1 => self.memory.load_u16(addr)? as i16 as i32 as u32,
+                    0x2 => self.memory.load_u32(addr)?,
+                    0x4 => self.memory.load_u8(addr)? as u32,
+                    0x5 => self.memory.load_u16(addr)? as u32,
+                    _ => return Err(illegal()),
+                };
+                self.set_reg(rd, value);
+            }
+            0b0010011 => {
+                let shamt = imm_i & 0x1f;
+                let value = match funct3 {
+                    0x0 => x1.wrapping_add(imm_i),
+                    0x1 => x1 << shamt,
+                    0x2 => ((x1 as i32) < (imm_i as i32)) as u32,
+                    0x3 => (x1 < imm_i) as u32,
+                    0x4 => x1 ^ imm_i,
+                    0x5 => {
+                        cycles = DIV_CYCLES;
+                        match funct7 {
+                            0x00 => x1 >> shamt,
+                            0x20 => ((x1 as i32) >> shamt) as u32,
+                            _ => return Err(illegal()),
+                        }
+                    }
+                    0x6 => x1 | imm_i,
+                    0x7 => x1 & imm_i,
+                    _ => return Err(illegal()),
+                };
+                self.set_reg(rd, value);
+            }
+            0b0010111 => self.set_reg(rd, self.pc.wrapping_add(imm_u)),
+            0b0100011 => {
+                let addr = x1.wrapping_add(imm_s);
+                match funct3 {
+                    0x0 => self.memory.store_u8(addr, x2 as u8)?,
+                    0x1 => self.memory.store_u16(addr, x2 as u16)?,
+                    0x2 => self.memory.store_u32(addr, x2)?,
+                    _ => return Err(illegal()),
+                }
+            }
+            0b0110011 => {
+                let shamt = x2 & 0x1f;
+                let value = match (funct3, funct7) {
+                    (0x0, 0x00) => x1.wrapping_add(x2),
+                    (0x0, 0x20) => x1.wrapping_sub(x2),
+                    (0x1, 0x00) => x1 << shamt,
+                    (0x2, 0x00) => ((x1 as i32) < (x2 as i32)) as u32,
+                    (0x3, 0x00) => (x1 < x2) as u32,
+                    (0x4, 0x00) => x1 ^ x2,
+                    (0x5, 0x00) => {
+                        cycles = DIV_CYCLES;
+                        x1 >> shamt
+                    }
+                    (0x5, 0x20) => {
+                        cycles = DIV_CYCLES;
+                        ((x1 as i32) >> shamt) as u32
+                    }
+                    (0x6, 0x00) => x1 | x2,
+                    (0x7, 0x00) => x1 & x2,
+                    (0x0, 0x01) => x1.wrapping_mul(x2),
+                    (0x1, 0x01) => ((x1 as i32 as i64 * x2 as i32 as i64) >> 32) as u32,
+                    (0x2, 0x01) => ((x1 as i32 as i64 * x2 as i64) >> 32) as u32,
+                    (0x3, 0x01) => ((x1 as u64 * x2 as u64) >> 32) as u32,
+                    (0x4..=0x7, 0x01) => {
+                        cycles = DIV_CYCLES;
+                        divide(funct3, x1, x2)
+                    }
+                    _ => return Err(illegal()),
+                };
+                self.set_reg(rd, value);
+            }
+            0b0110111 => self.set_reg(rd, imm_u),
+            0b1100011 => {
+                let taken = match funct3 {
+                    0x0 => x1 == x2,
+                    0x1 => x1 != x2,
+                    0x4 => (x1 as i32) < (x2 as i32),
+                    0x5 => (x1 as i32) >= (x2 as i32),
+                    0x6 => x1 < x2,
+                    0x7 => x1 >= x2,
+                    _ => return Err(illegal()),
+                };
+                if taken {
+                    next_pc = self.pc.wrapping_add(imm_b);
+                }
+            }
+            0b1100111 if funct3 == 0 => {
+                next_pc = x1.wrapping_add(imm_i) & !1;
+                self.set_reg(rd, self.pc.wrapping_add(WORD_SIZE as u32));
+            }
+            0b1101111 => {
+                next_pc = self.pc.wrapping_add(imm_j);
+                self.set_reg(rd, self.pc.wrapping_add(WORD_SIZE as u32));
+            }
+            0b1110011 if funct3 == 0 && funct7 == 0 && rs2 == 0 => {
+                cycles = self.ecall()?;
+            }
+            _ => return Err(illegal()),
+        }
+
+        self.body_cycles += cycles;
+        self.pc = next_pc;
+        Ok(())
+    }
+
+    fn ecall(&mut self) -> Result<usize> {
+        match self.regs[REG_T0] {
+            ecall::HALT => {
+                let exit_code = ExitCode::from_words([self.regs[REG_A0], self.regs[REG_A1]])?;
+                self.handler.on_halt(exit_code)?;
+                self.halted = true;
+                Ok(INSN_CYCLES)
+            }
+            ecall::OUTPUT => Ok(INSN_CYCLES),
+            ecall::SOFTWARE => {
+                let (a0, a1) = self.syscall()?;
+                self.set_reg(REG_A0, a0);
+                self.set_reg(REG_A1, a1);
+                Ok(ECALL_CYCLES)
+            }
+            ecall::SHA => {
+                let load_digest = |addr| -> Result<Digest> {
+                    Ok(Digest::from_slice(&self.memory.load_region_u32(
+                        addr,
+                        (DIGEST_WORDS * WORD_SIZE) as u32,
+                    )?))
+                };
+                let out_state = self.regs[REG_A0];
+                let in_state = load_digest(self.regs[REG_A1])?;
+                let block1 = load_digest(self.regs[REG_A2])?;
+                let block2 = load_digest(self.regs[REG_A3])?;
+                let result = sha().compress(&in_state, &block1, &block2);
+                self.memory.store_region_u32(out_state, result.as_slice())?;
+                Ok(SHA_CYCLES)
+            }
+            ecall::FFPU => bail!("FFPU ecall is not supported in execute-only mode"),
+            kind => Err(ExecutionError::UnknownEcall { kind }.into()),
+        }
+    }
+
+    fn syscall(&mut self) -> Result<(u32, u32)> {
+        let cycle = self.cycle();
+        let nr = self.regs[REG_A7];
+        match nr {
+            SYS_PANIC => {
+                let buf = self
+                    .memory
+                    .load_region(self.regs[REG_A0], self.regs[REG_A1])?;
+                let msg = String::from_utf8_lossy(&buf).into_owned();
+                debug!("SYS_PANIC[{cycle}]> {msg}");
+                self.handler.on_fault(&msg)?;
+                Err(ExecutionError::GuestPanic {
+                    msg,
+                    pc: self.pc,
+                    cycle,
+                }
+                .into())
+            }
+            SYS_LOG => {
+                let buf = self
+                    .memory
+                    .load_region(self.regs[REG_A0], self.regs[REG_A1])?;
+                println!("R0VM[{cycle}] {}", String::from_utf8_lossy(&buf));
+                Ok((0, 0))
+            }
+            SYS_IO => {
+                let channel = self.regs[REG_A0];
+                let out_ptr = self.regs[REG_A3];
+                debug!("SYS_IO[{cycle}]");
+                let buf = self
+                    .memory
+                    .load_region(self.regs[REG_A1], self.regs[REG_A2])?;
+                let result = self.handler.on_txrx(channel, &buf)?;
+                check_input_region(out_ptr, result.len())?;
+                self.memory.store_region(out_ptr, &result)?;
+                Ok((result.len() as u32, 0))
+            }
+            SYS_READ => {
+                let fd = self.regs[REG_A0];
+                let buf_ptr = self.regs[REG_A1];
+                let buf_len = self.regs[REG_A2];
+                debug!("SYS_READ[{cycle}]> fd: {fd}, len: {buf_len}");
+                let data = self.handler.on_read(fd, buf_len as usize)?;
+                if data.len() > buf_len as usize {
+                    bail!(
+                        "Host returned {} bytes for a {buf_len} byte read",
+                        data.len()
+                    );
+                }
+                if region_end(buf_ptr, buf_len)? as usize > MEM_SIZE {
+                    return Err(fault(buf_ptr, MemoryFaultKind::OutOfRange));
+                }
+                self.memory.store_region(buf_ptr, &data)?;
+                Ok((data.len() as u32, 0))
+            }
+            SYS_RANDOM => {
+                let buf_ptr = self.regs[REG_A0];
+                let buf_len = self.regs[REG_A1];
+                debug!("SYS_RANDOM[{cycle}]> len: {buf_len}");
+                if region_end(buf_ptr, buf_len)? as usize > MEM_SIZE {
+                    return Err(fault(buf_ptr, MemoryFaultKind::OutOfRange));
+                }
+                let data = self.handler.on_random(buf_len as usize)?;
+                if data.len() != buf_len as usize {
+                    bail!(
+                        "Host returned {} random bytes, but {buf_len} were requested",
+                        data.len()
+                    );
+                }
+                self.memory.store_region(buf_ptr, &data)?;
+                Ok((0, 0))
+            }
+            SYS_ARGC => {
+                let argc = self.handler.on_argc()?;
+                debug!("SYS_ARGC[{cycle}]> argc = {argc}");
+                Ok((argc, 0))
+            }
+            SYS_ARGV => {
+                let index = self.regs[REG_A0];
+                let buf_ptr = self.regs[REG_A1];
+                let buf_len = self.regs[REG_A2];
+                debug!("SYS_ARGV[{cycle}]> index: {index}, len: {buf_len}");
+                let arg = self.handler.on_argv(index)?;
+                let data = &arg[..arg.len().min(buf_len as usize)];
+                if region_end(buf_ptr, data.len() as u32)? as usize > MEM_SIZE {
+                    return Err(fault(buf_ptr, MemoryFaultKind::OutOfRange));
+                }
+                self.memory.store_region(buf_ptr, data)?;
+                Ok((arg.len() as u32, 0))
+            }
+            SYS_GETENV => {
+                let name_ptr = self.regs[REG_A0];
+                let name_len = self.regs[REG_A1];
+                let buf_ptr = self.regs[REG_A2];
+                let buf_len = self.regs[REG_A3];
+                let name = self.memory.load_region(name_ptr, name_len)?;
+                let name = String::from_utf8_lossy(&name);
+                debug!("SYS_GETENV[{cycle}]> {name}");
+                let value = match self.handler.on_getenv(&name)? {
+                    Some(value) => value,
+                    None => return Ok((u32::MAX, 0)),
+                };
+                let data = &value[..value.len().min(buf_len as usize)];
+                if region_end(buf_ptr, data.len() as u32)? as usize > MEM_SIZE {
+                    return Err(fault(buf_ptr, MemoryFaultKind::OutOfRange));
+                }
+                self.memory.store_region(buf_ptr, data)?;
+                Ok((value.len() as u32, 0))
+            }
+            SYS_COMMIT => {
+                let buf_ptr = self.regs[REG_A0];
+                let buf_len = self.regs[REG_A1];
+                debug!("SYS_COMMIT[{cycle}]> 0x{buf_ptr:08X} : {buf_len}");
+                let buf = self.memory.load_region_u32(buf_ptr, buf_len)?;
+                self.handler.on_commit(&buf)?;
+                Ok((0, 0))
+            }
+            SYS_CYCLE_COUNT => {
+                debug!("SYS_CYCLE_COUNT[{cycle}]> cycle = {cycle}");
+                Ok((cycle as u32, 0))
+            }
+            SYS_PAUSE_REQUESTED => {
+                let pause = self.handler.on_pause_requested(cycle)?;
+                debug!("SYS_PAUSE_REQUESTED[{cycle}]> {pause}");
+                Ok((pause as u32, 0))
+            }
+            SYS_PAUSE => {
+                debug!("SYS_PAUSE[{cycle}]>");
+                let layout = self
+                    .memory
+                    .load_region_u32(self.regs[REG_A0], self.regs[REG_A1] * WORD_SIZE as u32)?;
+                let digest = self
+                    .memory
+                    .load_region_u32(self.regs[REG_A2], (DIGEST_WORDS * WORD_SIZE) as u32)?;
+                let state =
+                    SavedState::load(layout, Digest::try_from_slice(&digest)?, |addr, len| {
+                        self.memory.load_region_u32(addr, len)
+                    })?;
+                self.handler.on_pause(state)?;
+                Ok((0, 0))
+            }
+            SYS_RESUME => {
+                let layout_ptr = self.regs[REG_A0];
+                let layout_words = self.regs[REG_A1];
+                let state = match self.handler.on_resume()? {
+                    Some(state) => state,
+                    None => return Ok((0, 0)),
+                };
+                debug!("SYS_RESUME[{cycle}]> {}", state.digest);
+                if state.layout.len() != layout_words as usize {
+                    bail!(
+                        "Saved layout has {} words, but the guest expects {layout_words}",
+                        state.layout.len()
+                    );
+                }
+                self.memory.store_region_u32(layout_ptr, &state.layout)?;
+                for (region, words) in state.layout.chunks_exact(2).zip(state.regions.iter()) {
+                    self.memory.store_region_u32(region[0], words)?;
+                }
+                Ok((1, 0))
+            }
+            SYS_COMPUTE_POLY => {
+                debug!("SYS_COMPUTE_POLY[{cycle}]>");
+                let eval_u: Vec<BabyBearExtElem> = self.memory.read_slice(self.regs[REG_A0])?;
+                let poly_mix = self.memory.read_value(self.regs[REG_A1])?;
+                let out: Vec<BabyBearElem> = self.memory.read_slice(self.regs[REG_A2])?;
+                let mix: Vec<BabyBearElem> = self.memory.read_slice(self.regs[REG_A3])?;
+
+                let args: &[&[BabyBearElem]] = &[&out, &mix];
+                let result = CIRCUIT.poly_ext(&poly_mix, &eval_u, args);
+
+                let words = result.tot.to_u32_words();
+                self.memory.store_region_u32(self.regs[REG_A4], &words)?;
+                Ok((words.len() as u32, 0))
+            }
+            _ => {
+                let mut ctx = EmulatorSyscallContext {
+                    regs: &self.regs,
+                    memory: &mut self.memory,
+                    cycle,
+                };
+                self.handler
+                    .on_syscall(nr, &mut ctx)?
+                    .ok_or_else(|| ExecutionError::UnknownSyscall { nr }.into())
+            }
+        }
+    }
+}
+
+// Implements DIV, DIVU, REM and REMU, including the RISC-V conventions for
+// division by zero and signed overflow.
+fn divide(funct3: u32, x1: u32, x2: u32) -> u32 {
+    match funct3 {
+        0x4 => match x2 {
+            0 => u32::MAX,
+            _ => (x1 as i32).wrapping_div(x2 as i32) as u32,
+        },
+        0x5 => match x2 {
+            0 => u32::MAX,
+            _ => x1 / x2,
+        },
+        0x6 => match x2 {
+            0 => x1,
+            _ => (x1 as i32).wrapping_rem(x2 as i32) as u32,
+        },
+        0x7 => match x2 {
+            0 => x1,
+            _ => x1 % x2,
+        },
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::divide;
+
+    #[test]
+    fn divide_edge_cases() {
+        // DIV
+        assert_eq!(divide(0x4, 7, 2), 3);
+        assert_eq!(divide(0x4, -7i32 as u32, 2), -3i32 as u32);
+        assert_eq!(divide(0x4, 7, 0), u32::MAX);
+        assert_eq!(divide(0x4, i32::MIN as u32, -1i32 as u32), i32::MIN as u32);
+        // DIVU
+        assert_eq!(divide(0x5, u32::MAX, 2), u32::MAX / 2);
+        assert_eq!(divide(0x5, 7, 0), u32::MAX);
+        // REM
+        assert_eq!(divide(0x6, -7i32 as u32, 2), -1i32 as u32);
+        assert_eq!(divide(0x6, 7, 0), 7);
+        assert_eq!(divide(0x6, i32::MIN as u32, -1i32 as u32), 0);
+        // REMU
+        assert_eq!(divide(0x7, 7, 2), 1);
+        assert_eq!(divide(0x7, 7, 0), 7);
+    }
+}
@@ -0,0 +1,76 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use thiserror::Error;
+
+/// The kind of memory access which caused a [ExecutionError::MemoryFault].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryFaultKind {
+    /// A load from an address not aligned to the size of the access.
+    UnalignedLoad,
+    /// A store to an address not aligned to the size of the access.
+    UnalignedStore,
+    /// An access to an address outside of guest memory.
+    OutOfRange,
+}
+
+impl core::fmt::Display for MemoryFaultKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MemoryFaultKind::UnalignedLoad => write!(f, "unaligned load"),
+            MemoryFaultKind::UnalignedStore => write!(f, "unaligned store"),
+            MemoryFaultKind::OutOfRange => write!(f, "address out of range"),
+        }
+    }
+}
+
+/// An error caused by the guest during execution.
+///
+/// These are returned by [crate::Prover::run] and [crate::Prover::execute]
+/// wrapped in an [anyhow::Error], and can be recovered with
+/// [anyhow::Error::downcast_ref].
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExecutionError {
+    /// The guest aborted with `risc0_zkvm::guest::abort`.  A guest which
+    /// panics halts with [crate::ExitCode::Faulted] instead, unless it panics
+    /// again while halting.
+    #[error("Guest panicked at pc 0x{pc:08X}, cycle {cycle}: {msg}")]
+    GuestPanic { msg: String, pc: u32, cycle: usize },
+
+    /// The guest tried to execute an invalid or unsupported instruction.
+    #[error("Illegal instruction 0x{word:08X} at pc 0x{pc:08X}")]
+    IllegalInstruction { word: u32, pc: u32 },
+
+    /// The guest made an invalid memory access.
+    #[error("Memory fault: {kind} at 0x{addr:08X}")]
+    MemoryFault { addr: u32, kind: MemoryFaultKind },
+
+    /// The guest invoked a syscall which the host does not support.
+    #[error("Unsupported syscall: {nr}")]
+    UnknownSyscall { nr: u32 },
+
+    /// The guest invoked an ecall which the host does not support.
+    #[error("Unsupported ecall: {kind}")]
+    UnknownEcall { kind: u32 },
+
+    /// The guest did not halt within the maximum number of cycles.
+    #[error("Cycle limit exceeded, max po2 of {max_po2} reached")]
+    CycleLimitExceeded { max_po2: usize },
+
+    /// The guest did not halt within the cycle budget set with
+    /// [crate::ProverOpts::with_max_cycles].
+    #[error("Cycle budget of {max_cycles} cycles exceeded")]
+    CycleBudgetExceeded { max_cycles: usize },
+}
@@ -14,39 +14,103 @@
 
 use std::collections::BTreeMap;
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use bytemuck::Pod;
 use lazy_regex::{regex, Captures};
 use log::{debug, trace};
 use risc0_circuit_rv32im::CircuitImpl;
 use risc0_zkp::{
     adapter::{CircuitStepHandler, PolyExt},
-    core::log2_ceil,
+    core::{
+        log2_ceil,
+        sha::{Digest, DIGEST_WORDS},
+    },
     field::{
         baby_bear::{BabyBear, BabyBearElem, BabyBearExtElem},
         Elem,
     },
     prove::executor::Executor,
-    MAX_CYCLES_PO2, ZK_CYCLES,
+    ZK_CYCLES,
 };
 use risc0_zkvm_platform::{
-    memory::{FFPU, SYSTEM},
+    memory::{FFPU, INPUT, MEM_SIZE, SYSTEM},
     syscall::{
-        nr::{SYS_COMMIT, SYS_COMPUTE_POLY, SYS_CYCLE_COUNT, SYS_IO, SYS_LOG, SYS_PANIC},
-        reg_abi::{REG_A0, REG_A1, REG_A2, REG_A3, REG_A4, REG_A7},
+        nr::{
+            SYS_ARGC, SYS_ARGV, SYS_COMMIT, SYS_COMPUTE_POLY, SYS_CYCLE_COUNT, SYS_GETENV, SYS_IO,
+            SYS_LOG, SYS_PANIC, SYS_PAUSE, SYS_PAUSE_REQUESTED, SYS_RANDOM, SYS_READ, SYS_RESUME,
+        },
+        reg_abi::{REG_A0, REG_A1, REG_A2, REG_A3, REG_A4, REG_A7, REG_T0},
     },
     WORD_SIZE,
 };
 
-use super::{elf::Program, loader::Loader, merge_word8, plonk, split_word8, TraceEvent};
-use crate::CIRCUIT;
+#[cfg(feature = "gdb")]
+use super::gdb::{GdbStub, GdbTarget};
+use super::{
+    elf::Program,
+    error::{ExecutionError, MemoryFaultKind},
+    loader::Loader,
+    merge_word8, plonk, split_word8,
+    stats::{MemoryStats, MemoryTracker, PAGE_SIZE},
+    syscall::{get_register, SyscallContext},
+    TraceEvent,
+};
+use crate::{receipt::ExitCode, CIRCUIT};
 
 pub trait HostHandler {
     fn is_trace_enabled(&self) -> bool;
     fn on_commit(&mut self, buf: &[u32]) -> Result<()>;
+    fn on_halt(&mut self, exit_code: ExitCode) -> Result<()>;
     fn on_fault(&mut self, msg: &str) -> Result<()>;
     fn on_txrx(&mut self, channel: u32, buf: &[u8]) -> Result<Vec<u8>>;
+    fn on_read(&mut self, fd: u32, nbytes: usize) -> Result<Vec<u8>>;
+    fn on_random(&mut self, nbytes: usize) -> Result<Vec<u8>>;
+    fn on_argc(&mut self) -> Result<u32>;
+    fn on_argv(&mut self, index: u32) -> Result<Vec<u8>>;
+    fn on_getenv(&mut self, name: &str) -> Result<Option<Vec<u8>>>;
     fn on_trace(&mut self, event: TraceEvent) -> Result<()>;
+    // Returns whether the guest should pause at a checkpoint it reached at
+    // the given cycle.
+    fn on_pause_requested(&mut self, cycle: usize) -> Result<bool>;
+    fn on_pause(&mut self, state: SavedState) -> Result<()>;
+    // Takes the state to resume the guest from, if any.
+    fn on_resume(&mut self) -> Result<Option<SavedState>>;
+    // Handles a syscall not built into the executor, returning None if no
+    // handler is registered for it.
+    fn on_syscall(&mut self, nr: u32, ctx: &mut dyn SyscallContext) -> Result<Option<(u32, u32)>>;
+    #[cfg(feature = "gdb")]
+    fn gdb_stub(&mut self) -> Option<&mut GdbStub>;
+}
+
+/// The state a guest saved when it paused, to be written back to its memory
+/// when it is resumed.
+#[derive(Clone, Debug)]
+pub struct SavedState {
+    // The start address and length in bytes of each region of memory holding
+    // the guest's state, as laid out by the guest.
+    pub layout: Vec<u32>,
+    pub regions: Vec<Vec<u32>>,
+    // The digest of the state, which the guest commits to.
+    pub digest: Digest,
+}
+
+impl SavedState {
+    // Reads the regions described by the layout from the guest's memory.
+    pub(super) fn load(
+        layout: Vec<u32>,
+        digest: Digest,
+        load_region: impl Fn(u32, u32) -> Result<Vec<u32>>,
+    ) -> Result<Self> {
+        let regions = layout
+            .chunks_exact(2)
+            .map(|region| load_region(region[0], region[1]))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            layout,
+            regions,
+            digest,
+        })
+    }
 }
 
 struct MemoryState {
@@ -63,50 +127,53 @@ struct MemoryState {
 }
 
 impl MemoryState {
-    #[track_caller]
-    fn load_u8(&self, addr: u32) -> u8 {
+    fn load_u8(&self, addr: u32) -> Result<u8> {
         // debug!("load_u8: 0x{addr:08X}");
         // align to the nearest word
         let aligned = addr & !(WORD_SIZE as u32 - 1);
         let offset = addr % WORD_SIZE as u32;
-        let word = self.load_u32(aligned);
-        ((word >> (offset * 8)) & 0xff) as u8
+        let word = self.load_u32(aligned)?;
+        Ok(((word >> (offset * 8)) & 0xff) as u8)
     }
 
-    #[track_caller]
-    fn load_u32(&self, addr: u32) -> u32 {
+    fn load_u32(&self, addr: u32) -> Result<u32> {
         // debug!("load_u32: 0x{addr:08X}");
-        assert_eq!(addr % WORD_SIZE as u32, 0, "unaligned load");
+        if addr % WORD_SIZE as u32 != 0 {
+            return Err(fault(addr, MemoryFaultKind::UnalignedLoad));
+        }
         let key = addr / 4;
         match self.ram.get(&key) {
-            Some(word) => *word,
-            None => panic!("addr out of range: 0x{addr:08X}"),
+            Some(word) => Ok(*word),
+            None => Err(fault(addr, MemoryFaultKind::OutOfRange)),
         }
     }
 
-    fn load_register(&self, num: usize) -> u32 {
+    fn load_register(&self, num: usize) -> Result<u32> {
         self.load_u32((SYSTEM.start() + num * 4) as u32)
     }
 
-    #[track_caller]
-    fn load_region_u32(&self, start: u32, size: u32) -> Vec<u32> {
-        (start..start + size)
+    fn load_registers(&self) -> Result<[u32; 32]> {
+        let mut regs = [0; 32];
+        for (num, reg) in regs.iter_mut().enumerate() {
+            *reg = self.load_register(num)?;
+        }
+        Ok(regs)
+    }
+
+    fn load_region_u32(&self, start: u32, size: u32) -> Result<Vec<u32>> {
+        (start..region_end(start, size)?)
             .step_by(WORD_SIZE)
             .map(|addr| self.load_u32(addr))
             .collect()
     }
 
-    #[track_caller]
-    fn load_region(&self, addr: u32, size: u32) -> Vec<u8> {
-        let mut region = Vec::new();
-        for addr in addr..addr + size {
-            region.push(self.load_u8(addr));
-        }
-        region
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>> {
+        (addr..region_end(addr, size)?)
+            .map(|addr| self.load_u8(addr))
+            .collect()
     }
 
-    #[track_caller]
-    fn store_u8(&mut self, addr: u32, value: u8) {
+    fn store_u8(&mut self, addr: u32, value: u8) -> Result<()> {
         // debug!("store_u8: 0x{addr:08X} <= 0x{value:08X}");
         // align to the nearest word
         let aligned = addr & !(WORD_SIZE as u32 - 1);
@@ -114,61 +181,137 @@ impl MemoryState {
         let key = aligned / 4;
         let mut word = self.ram.get(&key).unwrap_or(&0) & !(0xff << (offset * 8));
         word |= (value as u32) << (offset * 8);
-        self.store_u32(aligned, word);
+        self.store_u32(aligned, word)
     }
 
-    #[track_caller]
-    fn store_u32(&mut self, addr: u32, value: u32) {
+    fn store_u32(&mut self, addr: u32, value: u32) -> Result<()> {
         // debug!("store_u32: 0x{addr:08X} <= 0x{value:08X}");
-        assert_eq!(addr % WORD_SIZE as u32, 0, "unaligned store");
+        if addr % WORD_SIZE as u32 != 0 {
+            return Err(fault(addr, MemoryFaultKind::UnalignedStore));
+        }
+        // The host may only write to guest memory, not the system region.
+        if addr as usize >= MEM_SIZE {
+            return Err(fault(addr, MemoryFaultKind::OutOfRange));
+        }
         let key = addr / 4;
         self.ram.insert(key, value);
+        Ok(())
     }
 
-    #[track_caller]
-    fn store_region(&mut self, addr: u32, slice: &[u8]) {
+    fn store_region(&mut self, addr: u32, slice: &[u8]) -> Result<()> {
         trace!("store_region: 0x{addr:08X} <= {} bytes", slice.len());
+        check_store(addr, slice.len())?;
         for i in 0..slice.len() {
-            self.store_u8(addr + i as u32, slice[i]);
+            self.store_u8(addr + i as u32, slice[i])?;
         }
+        Ok(())
     }
 
-    #[track_caller]
-    fn store_region_u32(&mut self, addr: u32, slice: &[u32]) {
-        assert!(addr % WORD_SIZE as u32 == 0);
+    fn store_region_u32(&mut self, addr: u32, slice: &[u32]) -> Result<()> {
+        check_store(addr, slice.len() * WORD_SIZE)?;
         for (offset, word) in slice.iter().enumerate() {
-            self.store_u32(addr + WORD_SIZE as u32 * offset as u32, *word);
+            self.store_u32(addr + WORD_SIZE as u32 * offset as u32, *word)?;
         }
+        Ok(())
     }
 
     // Reads a slice of data from the guest's memory and interprets it
     // as the given type, which must be plain old data..  The address
     // provided is the guest's address of a SliceDescriptor structure.
-    fn read_slice<T: Pod>(&self, desc_addr: u32) -> Vec<T> {
-        let desc_bytes = self.load_region(desc_addr, 8);
+    fn read_slice<T: Pod>(&self, desc_addr: u32) -> Result<Vec<T>> {
+        let desc_bytes = self.load_region(desc_addr, 8)?;
         let size: u32 = *bytemuck::from_bytes(&desc_bytes[..4]);
         let addr: u32 = *bytemuck::from_bytes(&desc_bytes[4..]);
         let elt_size = core::mem::size_of::<T>();
         assert_eq!(elt_size % WORD_SIZE, 0, "T should be word aligned");
-        assert_eq!(
-            size as usize % elt_size,
-            0,
-            "slice does not end on a boundary of T; size={size}, elt_size={elt_size}"
-        );
-        let bytes = self.load_region(addr, size);
-        bytes
+        if size as usize % elt_size != 0 {
+            bail!("slice does not end on a boundary of T; size={size}, elt_size={elt_size}");
+        }
+        let bytes = self.load_region(addr, size)?;
+        Ok(bytes
             .chunks_exact(elt_size)
             .map(|chunk| *bytemuck::from_bytes(chunk))
-            .collect()
+            .collect())
     }
 
     // Reads words from guest's memory and transmutes it into the given value,
     // which should be repr(C) and plain old data.
-    fn read_value<T: Pod>(&self, addr: u32) -> T {
+    fn read_value<T: Pod>(&self, addr: u32) -> Result<T> {
         let size = core::mem::size_of::<T>();
         assert_eq!(size % WORD_SIZE, 0, "T should be word aligned");
-        let bytes = self.load_region(addr, size as u32);
-        *bytemuck::from_bytes(&bytes)
+        let bytes = self.load_region(addr, size as u32)?;
+        Ok(*bytemuck::from_bytes(&bytes))
+    }
+}
+
+// Checks that a write by the host lies entirely within guest memory, before
+// any of it is written.
+pub fn check_store(addr: u32, len: usize) -> Result<()> {
+    if region_end(addr, len as u32)? as usize > MEM_SIZE {
+        return Err(fault(addr, MemoryFaultKind::OutOfRange));
+    }
+    Ok(())
+}
+
+pub fn fault(addr: u32, kind: MemoryFaultKind) -> anyhow::Error {
+    ExecutionError::MemoryFault { addr, kind }.into()
+}
+
+// Replies to SYS_IO are written to the input region; make sure a reply of
+// `len` bytes at `addr` doesn't overrun it.
+pub fn check_input_region(addr: u32, len: usize) -> Result<()> {
+    let end = region_end(addr, len as u32)? as usize;
+    if (addr as usize) < INPUT.start() || end > INPUT.end() {
+        return Err(fault(addr, MemoryFaultKind::OutOfRange));
+    }
+    Ok(())
+}
+
+// Returns the end of a guest memory region, or a fault if the region wraps
+// around the address space.
+pub fn region_end(addr: u32, size: u32) -> Result<u32> {
+    addr.checked_add(size)
+        .ok_or_else(|| fault(addr, MemoryFaultKind::OutOfRange))
+}
+
+// Gives syscall handlers access to the guest state held by a
+// MachineContext.
+struct MachineSyscallContext<'a> {
+    regs: [u32; 32],
+    memory: &'a mut MemoryState,
+    cycle: usize,
+}
+
+impl<'a> SyscallContext for MachineSyscallContext<'a> {
+    fn load_register(&self, reg: usize) -> Result<u32> {
+        get_register(&self.regs, reg)
+    }
+
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>> {
+        self.memory.load_region(addr, size)
+    }
+
+    fn store_region(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        self.memory.store_region(addr, data)
+    }
+
+    fn cycle(&self) -> usize {
+        self.cycle
+    }
+}
+
+#[cfg(feature = "gdb")]
+impl GdbTarget for MemoryState {
+    fn load_register(&self, reg: usize) -> Result<u32> {
+        MemoryState::load_register(self, reg)
+    }
+
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>> {
+        MemoryState::load_region(self, addr, size)
+    }
+
+    fn store_region(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        MemoryState::store_region(self, addr, data)
     }
 }
 
@@ -176,8 +319,10 @@ pub struct MachineContext<'a, H: HostHandler> {
     memory: MemoryState,
     handler: &'a mut H,
     trace_enabled: bool,
-    halted: bool,
+    // The cycle at which the guest halted, if it has.
+    halt_cycle: Option<usize>,
     pc: u32,
+    stats: MemoryTracker,
 }
 
 #[derive(Debug)]
@@ -216,21 +361,40 @@ impl<'a, H: HostHandler> CircuitStepHandler<BabyBearElem> for MachineContext<'a,
     ) -> Result<()> {
         match name {
             "halt" => {
-                if !self.halted {
+                if self.halt_cycle.is_none() {
                     debug!("HALT: {cycle}");
+                    let exit_code = ExitCode::from_words([
+                        self.memory.load_register(REG_A0)?,
+                        self.memory.load_register(REG_A1)?,
+                    ])?;
+                    self.handler.on_halt(exit_code)?;
+                    if self.trace_enabled {
+                        self.handler.on_trace(TraceEvent::Halt {
+                            cycle: cycle as u32,
+                        })?;
+                    }
+                    #[cfg(feature = "gdb")]
+                    if let Some(stub) = self.handler.gdb_stub() {
+                        stub.on_halt()?;
+                    }
                 }
-                self.halted = true;
+                self.halt_cycle.get_or_insert(cycle);
                 Ok(())
             }
             "trace" => self.trace(cycle, args[0]),
             "getMajor" => {
-                let opcode = self.decode((args[0], args[1], args[2], args[3]));
+                let opcode = self.decode((args[0], args[1], args[2], args[3]))?;
                 outs[0] = BabyBearElem::new(opcode.major);
                 trace!("decode: {}", opcode.mnemonic);
+                if self.trace_enabled && opcode.mnemonic == "ECALL" {
+                    self.handler.on_trace(TraceEvent::Ecall {
+                        kind: self.memory.load_register(REG_T0)?,
+                    })?;
+                }
                 Ok(())
             }
             "getMinor" => {
-                let opcode = self.decode((args[0], args[1], args[2], args[3]));
+                let opcode = self.decode((args[0], args[1], args[2], args[3]))?;
                 outs[0] = BabyBearElem::new(opcode.minor);
                 Ok(())
             }
@@ -250,7 +414,7 @@ impl<'a, H: HostHandler> CircuitStepHandler<BabyBearElem> for MachineContext<'a,
                 Ok(())
             }
             "ramRead" => {
-                (outs[0], outs[1], outs[2], outs[3]) = self.ram_read(args[0]);
+                (outs[0], outs[1], outs[2], outs[3]) = self.ram_read(args[0])?;
                 Ok(())
             }
             "plonkWrite" => {
@@ -316,8 +480,9 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
             memory: MemoryState::new(),
             trace_enabled: io.is_trace_enabled(),
             handler: io,
-            halted: false,
+            halt_cycle: None,
             pc: 0x00000000,
+            stats: MemoryTracker::new(),
         }
     }
 
@@ -329,6 +494,10 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
                 pc: self.pc,
             })?
         }
+        #[cfg(feature = "gdb")]
+        if let Some(stub) = self.handler.gdb_stub() {
+            stub.on_instruction(self.pc, &mut self.memory)?;
+        }
         Ok(())
     }
 
@@ -451,26 +620,47 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
         trace!("{}", formatted);
     }
 
+    // Reports the first access to each page of memory in the trace.
+    fn touch_page(&mut self, addr: u32, first: bool) -> Result<()> {
+        if first && self.trace_enabled {
+            self.handler.on_trace(TraceEvent::PageTouched {
+                addr: addr & !(PAGE_SIZE - 1),
+            })?;
+        }
+        Ok(())
+    }
+
     fn ram_read(
         &mut self,
         addr: BabyBearElem,
-    ) -> (BabyBearElem, BabyBearElem, BabyBearElem, BabyBearElem) {
+    ) -> Result<(BabyBearElem, BabyBearElem, BabyBearElem, BabyBearElem)> {
         let addr: u32 = addr.into();
         if addr as usize * 4 >= FFPU.start() {
             let ffpu_addr = addr as usize - FFPU.start() / 4;
             if ffpu_addr >= self.memory.ffpu_ram.len() {
-                return (
+                return Ok((
                     BabyBearElem::ZERO,
                     BabyBearElem::ZERO,
                     BabyBearElem::ZERO,
                     BabyBearElem::ZERO,
-                );
+                ));
             }
-            return self.memory.ffpu_ram[ffpu_addr];
+            Ok(self.memory.ffpu_ram[ffpu_addr])
         } else {
             let data = *self.memory.ram.entry(addr).or_insert(0);
             // debug!("data: 0x{data:08X}");
-            split_word8(data)
+            let addr = addr * 4;
+            // Register reads are implied by the instructions, and registers
+            // aren't part of the guest's memory.
+            if !(SYSTEM.start()..SYSTEM.end()).contains(&(addr as usize)) {
+                let first = self.stats.touch(addr);
+                self.touch_page(addr, first)?;
+                if self.trace_enabled {
+                    self.handler
+                        .on_trace(TraceEvent::MemoryRead { addr, value: data })?;
+                }
+            }
+            Ok(split_word8(data))
         }
     }
 
@@ -491,6 +681,13 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
             let data = merge_word8(data);
             // debug!("ram_write> 0x{:08X} <= 0x{:08X}", addr * 4, data);
             self.memory.ram.insert(addr, data);
+            if (addr * 4) as usize >= SYSTEM.start() {
+                let reg = (addr * 4) as usize - SYSTEM.start();
+                self.stats.set_register(reg / 4, data);
+            } else {
+                let first = self.stats.write(addr * 4);
+                self.touch_page(addr * 4, first)?;
+            }
             if self.trace_enabled {
                 let addr = (addr * 4) as usize;
                 if addr >= SYSTEM.start() && addr < SYSTEM.end() {
@@ -550,43 +747,152 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
         (BabyBearElem, BabyBearElem, BabyBearElem, BabyBearElem),
         (BabyBearElem, BabyBearElem, BabyBearElem, BabyBearElem),
     )> {
-        let nr = self.memory.load_register(REG_A7);
+        let nr = self.memory.load_register(REG_A7)?;
+        if self.trace_enabled {
+            let mut args = [0; 5];
+            for (arg, reg) in args
+                .iter_mut()
+                .zip([REG_A0, REG_A1, REG_A2, REG_A3, REG_A4])
+            {
+                *arg = self.memory.load_register(reg)?;
+            }
+            self.handler
+                .on_trace(TraceEvent::SyscallStart { nr, args })?;
+        }
+        let (a0, a1) = self.dispatch_syscall(nr, cycle)?;
+        if self.trace_enabled {
+            self.handler.on_trace(TraceEvent::SyscallEnd {
+                ret: [merge_word8(a0), merge_word8(a1)],
+            })?;
+        }
+        Ok((a0, a1))
+    }
+
+    fn dispatch_syscall(
+        &mut self,
+        nr: u32,
+        cycle: usize,
+    ) -> Result<(
+        (BabyBearElem, BabyBearElem, BabyBearElem, BabyBearElem),
+        (BabyBearElem, BabyBearElem, BabyBearElem, BabyBearElem),
+    )> {
         match nr {
             SYS_PANIC => {
-                let msg_ptr = self.memory.load_register(REG_A0);
-                let msg_len = self.memory.load_register(REG_A1);
-                let buf = self.memory.load_region(msg_ptr, msg_len);
-                let str = String::from_utf8(buf).unwrap();
-                debug!("SYS_PANIC[{cycle}]> {str}");
-                self.handler.on_fault(&str)?;
-                Ok((split_word8(0), split_word8(0)))
+                let msg_ptr = self.memory.load_register(REG_A0)?;
+                let msg_len = self.memory.load_register(REG_A1)?;
+                let buf = self.memory.load_region(msg_ptr, msg_len)?;
+                let msg = String::from_utf8_lossy(&buf).into_owned();
+                debug!("SYS_PANIC[{cycle}]> {msg}");
+                self.handler.on_fault(&msg)?;
+                Err(ExecutionError::GuestPanic {
+                    msg,
+                    pc: self.pc,
+                    cycle,
+                }
+                .into())
             }
             SYS_LOG => {
-                let msg_ptr = self.memory.load_register(REG_A0);
-                let msg_len = self.memory.load_register(REG_A1);
-                let buf = self.memory.load_region(msg_ptr, msg_len);
-                let str = String::from_utf8(buf).unwrap();
-                println!("R0VM[{cycle}] {}", str);
+                let msg_ptr = self.memory.load_register(REG_A0)?;
+                let msg_len = self.memory.load_register(REG_A1)?;
+                let buf = self.memory.load_region(msg_ptr, msg_len)?;
+                println!("R0VM[{cycle}] {}", String::from_utf8_lossy(&buf));
                 Ok((split_word8(0), split_word8(0)))
             }
             SYS_IO => {
-                let channel = self.memory.load_register(REG_A0);
-                let buf_ptr = self.memory.load_register(REG_A1);
-                let buf_len = self.memory.load_register(REG_A2);
-                let out_ptr = self.memory.load_register(REG_A3);
+                let channel = self.memory.load_register(REG_A0)?;
+                let buf_ptr = self.memory.load_register(REG_A1)?;
+                let buf_len = self.memory.load_register(REG_A2)?;
+                let out_ptr = self.memory.load_register(REG_A3)?;
                 debug!("SYS_IO[{cycle}]");
 
-                let buf = self.memory.load_region(buf_ptr, buf_len);
+                let buf = self.memory.load_region(buf_ptr, buf_len)?;
                 let result = self.handler.on_txrx(channel, &buf)?;
-                self.memory.store_region(out_ptr, &result);
+                check_input_region(out_ptr, result.len())?;
+                self.memory.store_region(out_ptr, &result)?;
 
                 Ok((split_word8(result.len() as u32), split_word8(0)))
             }
+            SYS_READ => {
+                let fd = self.memory.load_register(REG_A0)?;
+                let buf_ptr = self.memory.load_register(REG_A1)?;
+                let buf_len = self.memory.load_register(REG_A2)?;
+                debug!("SYS_READ[{cycle}]> fd: {fd}, len: {buf_len}");
+
+                let data = self.handler.on_read(fd, buf_len as usize)?;
+                if data.len() > buf_len as usize {
+                    bail!(
+                        "Host returned {} bytes for a {buf_len} byte read",
+                        data.len()
+                    );
+                }
+                if region_end(buf_ptr, buf_len)? as usize > MEM_SIZE {
+                    return Err(fault(buf_ptr, MemoryFaultKind::OutOfRange));
+                }
+                self.memory.store_region(buf_ptr, &data)?;
+                Ok((split_word8(data.len() as u32), split_word8(0)))
+            }
+            SYS_RANDOM => {
+                let buf_ptr = self.memory.load_register(REG_A0)?;
+                let buf_len = self.memory.load_register(REG_A1)?;
+                debug!("SYS_RANDOM[{cycle}]> len: {buf_len}");
+                if region_end(buf_ptr, buf_len)? as usize > MEM_SIZE {
+                    return Err(fault(buf_ptr, MemoryFaultKind::OutOfRange));
+                }
+                let data = self.handler.on_random(buf_len as usize)?;
+                if data.len() != buf_len as usize {
+                    bail!(
+                        "Host returned {} random bytes, but {buf_len} were requested",
+                        data.len()
+                    );
+                }
+                self.memory.store_region(buf_ptr, &data)?;
+                Ok((split_word8(0), split_word8(0)))
+            }
+            SYS_ARGC => {
+                let argc = self.handler.on_argc()?;
+                debug!("SYS_ARGC[{cycle}]> argc = {argc}");
+                Ok((split_word8(argc), split_word8(0)))
+            }
+            SYS_ARGV => {
+                let index = self.memory.load_register(REG_A0)?;
+                let buf_ptr = self.memory.load_register(REG_A1)?;
+                let buf_len = self.memory.load_register(REG_A2)?;
+                debug!("SYS_ARGV[{cycle}]> index: {index}, len: {buf_len}");
+                let arg = self.handler.on_argv(index)?;
+                let data = &arg[..arg.len().min(buf_len as usize)];
+                if region_end(buf_ptr, data.len() as u32)? as usize > MEM_SIZE {
+                    return Err(fault(buf_ptr, MemoryFaultKind::OutOfRange));
+                }
+                self.memory.store_region(buf_ptr, data)?;
+                Ok((split_word8(arg.len() as u32), split_word8(0)))
+            }
+            SYS_GETENV => {
+                let name_ptr = self.memory.load_register(REG_A0)?;
+                let name_len = self.memory.load_register(REG_A1)?;
+                let buf_ptr = self.memory.load_register(REG_A2)?;
+                let buf_len = self.memory.load_register(REG_A3)?;
+                let name = self.memory.load_region(name_ptr, name_len)?;
+                let name = String::from_utf8_lossy(&name);
+                debug!("SYS_GETENV[{cycle}]> {name}");
+                let value = match self.handler.on_getenv(&name)? {
+                    Some(value) => value,
+                    None => return Ok((split_word8(u32::MAX), split_word8(0))),
+                };
+                let data = &value[..value.len().min(buf_len as usize)];
+                if region_end(buf_ptr, data.len() as u32)? as usize > MEM_SIZE {
+                    return Err(fault(buf_ptr, MemoryFaultKind::OutOfRange));
+                }
+                self.memory.store_region(buf_ptr, data)?;
+                Ok((split_word8(value.len() as u32), split_word8(0)))
+            }
             SYS_COMMIT => {
-                let buf_ptr = self.memory.load_register(REG_A0);
-                let buf_len = self.memory.load_register(REG_A1);
+                let buf_ptr = self.memory.load_register(REG_A0)?;
+                let buf_len = self.memory.load_register(REG_A1)?;
                 debug!("SYS_COMMIT[{cycle}]> 0x{buf_ptr:08X} : {buf_len}");
-                let buf = self.memory.load_region_u32(buf_ptr, buf_len);
+                let buf = self.memory.load_region_u32(buf_ptr, buf_len)?;
+                if self.trace_enabled {
+                    self.handler.on_trace(TraceEvent::Commit { len: buf_len })?;
+                }
                 self.handler.on_commit(buf.as_slice())?;
                 Ok((split_word8(0), split_word8(0)))
             }
@@ -594,46 +900,103 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
                 debug!("SYS_CYCLE_COUNT[{cycle}]> cycle = {cycle}");
                 Ok((split_word8(cycle as u32), split_word8(0)))
             }
+            SYS_PAUSE_REQUESTED => {
+                let pause = self.handler.on_pause_requested(cycle)?;
+                debug!("SYS_PAUSE_REQUESTED[{cycle}]> {pause}");
+                Ok((split_word8(pause as u32), split_word8(0)))
+            }
+            SYS_PAUSE => {
+                let layout_ptr = self.memory.load_register(REG_A0)?;
+                let layout_words = self.memory.load_register(REG_A1)?;
+                let digest_ptr = self.memory.load_register(REG_A2)?;
+                debug!("SYS_PAUSE[{cycle}]>");
+                let layout = self
+                    .memory
+                    .load_region_u32(layout_ptr, layout_words * WORD_SIZE as u32)?;
+                let digest = self
+                    .memory
+                    .load_region_u32(digest_ptr, (DIGEST_WORDS * WORD_SIZE) as u32)?;
+                let state =
+                    SavedState::load(layout, Digest::try_from_slice(&digest)?, |addr, len| {
+                        self.memory.load_region_u32(addr, len)
+                    })?;
+                self.handler.on_pause(state)?;
+                Ok((split_word8(0), split_word8(0)))
+            }
+            SYS_RESUME => {
+                let layout_ptr = self.memory.load_register(REG_A0)?;
+                let layout_words = self.memory.load_register(REG_A1)?;
+                let state = match self.handler.on_resume()? {
+                    Some(state) => state,
+                    None => return Ok((split_word8(0), split_word8(0))),
+                };
+                debug!("SYS_RESUME[{cycle}]> {}", state.digest);
+                if state.layout.len() != layout_words as usize {
+                    bail!(
+                        "Saved layout has {} words, but the guest expects {layout_words}",
+                        state.layout.len()
+                    );
+                }
+                self.memory.store_region_u32(layout_ptr, &state.layout)?;
+                for (region, words) in state.layout.chunks_exact(2).zip(state.regions.iter()) {
+                    self.memory.store_region_u32(region[0], words)?;
+                }
+                Ok((split_word8(1), split_word8(0)))
+            }
             SYS_COMPUTE_POLY => {
-                let eval_u_ptr = self.memory.load_register(REG_A0);
-                let poly_mix_ptr = self.memory.load_register(REG_A1);
-                let out_ptr = self.memory.load_register(REG_A2);
-                let mix_ptr = self.memory.load_register(REG_A3);
-                let result_ptr = self.memory.load_register(REG_A4);
+                let eval_u_ptr = self.memory.load_register(REG_A0)?;
+                let poly_mix_ptr = self.memory.load_register(REG_A1)?;
+                let out_ptr = self.memory.load_register(REG_A2)?;
+                let mix_ptr = self.memory.load_register(REG_A3)?;
+                let result_ptr = self.memory.load_register(REG_A4)?;
                 debug!("SYS_COMPUTE_POLY[{cycle}]>");
 
-                let eval_u: Vec<BabyBearExtElem> = self.memory.read_slice(eval_u_ptr);
-                let poly_mix = self.memory.read_value(poly_mix_ptr);
-                let out: Vec<BabyBearElem> = self.memory.read_slice(out_ptr);
-                let mix: Vec<BabyBearElem> = self.memory.read_slice(mix_ptr);
+                let eval_u: Vec<BabyBearExtElem> = self.memory.read_slice(eval_u_ptr)?;
+                let poly_mix = self.memory.read_value(poly_mix_ptr)?;
+                let out: Vec<BabyBearElem> = self.memory.read_slice(out_ptr)?;
+                let mix: Vec<BabyBearElem> = self.memory.read_slice(mix_ptr)?;
 
                 let args: &[&[BabyBearElem]] = &[&out, &mix];
                 let result = CIRCUIT.poly_ext(&poly_mix, &eval_u, args);
 
                 let words = result.tot.to_u32_words();
-                self.memory.store_region_u32(result_ptr, &words);
+                self.memory.store_region_u32(result_ptr, &words)?;
                 Ok((split_word8(words.len() as u32), split_word8(0)))
             }
-            _ => bail!("Unsupported syscall: {nr}"),
+            _ => {
+                let mut ctx = MachineSyscallContext {
+                    regs: self.memory.load_registers()?,
+                    memory: &mut self.memory,
+                    cycle,
+                };
+                match self.handler.on_syscall(nr, &mut ctx)? {
+                    Some((a0, a1)) => Ok((split_word8(a0), split_word8(a1))),
+                    None => Err(ExecutionError::UnknownSyscall { nr }.into()),
+                }
+            }
         }
     }
 
-    fn decode(&self, word: (BabyBearElem, BabyBearElem, BabyBearElem, BabyBearElem)) -> OpCode {
+    fn decode(
+        &self,
+        word: (BabyBearElem, BabyBearElem, BabyBearElem, BabyBearElem),
+    ) -> Result<OpCode> {
         let word = merge_word8(word);
         let opcode = word & 0x0000007f;
         let rs2 = (word & 0x01f00000) >> 20;
         let funct3 = (word & 0x00007000) >> 12;
         let funct7 = (word & 0xfe000000) >> 25;
         // debug!("decode: 0x{word:08X}");
+        let illegal = || ExecutionError::IllegalInstruction { word, pc: self.pc };
 
-        match opcode {
+        Ok(match opcode {
             0b0000011 => match funct3 {
                 0x0 => OpCode::new("LB", 24),
                 0x1 => OpCode::new("LH", 25),
                 0x2 => OpCode::new("LW", 26),
                 0x4 => OpCode::new("LBU", 27),
                 0x5 => OpCode::new("LHU", 28),
-                _ => unreachable!(),
+                _ => return Err(illegal().into()),
             },
             0b0010011 => match funct3 {
                 0x0 => OpCode::new("ADDI", 7),
@@ -644,18 +1007,18 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
                 0x5 => match funct7 {
                     0x00 => OpCode::new("SRLI", 46),
                     0x20 => OpCode::new("SRAI", 47),
-                    _ => unreachable!(),
+                    _ => return Err(illegal().into()),
                 },
                 0x6 => OpCode::new("ORI", 9),
                 0x7 => OpCode::new("ANDI", 10),
-                _ => unreachable!(),
+                _ => return Err(illegal().into()),
             },
             0b0010111 => OpCode::new("AUIPC", 22),
             0b0100011 => match funct3 {
                 0x0 => OpCode::new("SB", 29),
                 0x1 => OpCode::new("SH", 30),
                 0x2 => OpCode::new("SW", 31),
-                _ => unreachable!(),
+                _ => return Err(illegal().into()),
             },
             0b0110011 => match (funct3, funct7) {
                 (0x0, 0x00) => OpCode::new("ADD", 0),
@@ -676,7 +1039,7 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
                 (0x5, 0x01) => OpCode::new("DIVU", 41),
                 (0x6, 0x01) => OpCode::new("REM", 42),
                 (0x7, 0x01) => OpCode::new("REMU", 43),
-                _ => unreachable!(),
+                _ => return Err(illegal().into()),
             },
             0b0110111 => OpCode::new("LUI", 21),
             0b1100011 => match funct3 {
@@ -686,51 +1049,108 @@ impl<'a, H: HostHandler> MachineContext<'a, H> {
                 0x5 => OpCode::new("BGE", 16),
                 0x6 => OpCode::new("BLTU", 17),
                 0x7 => OpCode::new("BGEU", 18),
-                _ => unreachable!(),
+                _ => return Err(illegal().into()),
             },
             0b1100111 => match funct3 {
                 0x0 => OpCode::new("JALR", 20),
-                _ => unreachable!(),
+                _ => return Err(illegal().into()),
             },
             0b1101111 => OpCode::new("JAL", 19),
             0b1110011 => match funct3 {
                 0x0 => match (rs2, funct7) {
                     (0x0, 0x0) => OpCode::with_major_minor("ECALL", 8, 0),
                     (0x1, 0x0) => OpCode::with_major_minor("EBREAK", 8, 1),
-                    _ => unreachable!(),
+                    _ => return Err(illegal().into()),
                 },
-                _ => unreachable!(),
+                _ => return Err(illegal().into()),
             },
-            _ => panic!("Illegal opcode: 0b{opcode:07b}"),
+            _ => return Err(illegal().into()),
+        })
+    }
+}
+
+/// Limits on the number of cycles a guest may run for.
+#[derive(Clone, Copy, Debug)]
+pub struct CycleLimits {
+    pub max_po2: usize,
+    pub max_cycles: Option<usize>,
+}
+
+impl CycleLimits {
+    // Returns an error if a guest which has run for `cycles` cycles, and
+    // needs a trace of `2^po2` cycles, has gone past the limits.
+    pub fn check(&self, cycles: usize, po2: usize) -> Result<()> {
+        if let Some(max_cycles) = self.max_cycles {
+            if cycles > max_cycles {
+                return Err(ExecutionError::CycleBudgetExceeded { max_cycles }.into());
+            }
+        }
+        if po2 > self.max_po2 {
+            return Err(ExecutionError::CycleLimitExceeded {
+                max_po2: self.max_po2,
+            }
+            .into());
         }
+        Ok(())
     }
 }
 
 pub struct RV32Executor<'a, H: HostHandler> {
     loader: Loader,
     entry: u32,
+    limits: CycleLimits,
     pub executor: Executor<BabyBear, CircuitImpl, MachineContext<'a, H>>,
 }
 
 impl<'a, H: HostHandler> RV32Executor<'a, H> {
-    pub fn new(circuit: &'static CircuitImpl, elf: &'a Program, io: &'a mut H) -> Self {
+    pub fn new(
+        circuit: &'static CircuitImpl,
+        elf: &'a Program,
+        io: &'a mut H,
+        limits: CycleLimits,
+    ) -> Self {
         debug!("image.size(): {}", elf.image.len());
         let machine = MachineContext::new(io);
         let min_po2 = log2_ceil(1570 + elf.image.len() / 3 + ZK_CYCLES);
-        let executor = Executor::new(circuit, machine, min_po2, MAX_CYCLES_PO2);
+        let executor = Executor::new(circuit, machine, min_po2, limits.max_po2);
         Self {
             loader: Loader::new(&elf.image),
             entry: elf.entry,
+            limits,
             executor,
         }
     }
 
+    /// Runs the guest until it halts, returning the number of cycles used,
+    /// including the cycles needed to load the program and finalize the
+    /// execution but not the padding up to the size of the trace.
     #[tracing::instrument(skip_all)]
     pub fn run(&mut self) -> Result<usize> {
-        let cycles = self
-            .loader
-            .load(self.entry, |chunk, fini| self.executor.step(chunk, fini))?;
+        let executor = &mut self.executor;
+        let limits = self.limits;
+        self.loader.load(self.entry, |chunk, fini| {
+            // Report running out of cycles as a guest error rather than
+            // letting the executor fail to expand.
+            let full = executor.cycle + fini + ZK_CYCLES >= executor.steps;
+            let po2 = if full && !executor.halted {
+                executor.po2 + 1
+            } else {
+                executor.po2
+            };
+            limits.check(executor.cycle, po2)?;
+            executor.step(chunk, fini)
+        })?;
         self.executor.finalize();
-        Ok(cycles)
+        let halt_cycle = self
+            .executor
+            .handler
+            .halt_cycle
+            .ok_or_else(|| anyhow!("Guest stopped without halting"))?;
+        Ok(halt_cycle + 1 + Loader::FINI_CYCLES + ZK_CYCLES)
+    }
+
+    /// Returns statistics on the guest's use of memory.
+    pub fn memory_stats(&self) -> MemoryStats {
+        self.executor.handler.stats.stats()
     }
 }
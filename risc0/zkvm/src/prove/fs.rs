@@ -0,0 +1,436 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The host side of the read-only filesystem guests access through
+//! [risc0_zkvm_platform::io::SENDRECV_CHANNEL_FS].
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::{self, ErrorKind},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use risc0_zkvm_platform::io::fs::*;
+use serde::{Deserialize, Serialize};
+
+// The first descriptor given to an open file, leaving room for stdin, stdout
+// and stderr.
+const FIRST_FD: u32 = 3;
+
+enum Entry {
+    // A file given in memory, by its key.
+    Memory(String),
+    // A file under the root directory, by its canonical host path, along
+    // with its length.
+    Host(PathBuf, u64),
+    Dir(BTreeMap<String, u32>),
+}
+
+struct OpenFile {
+    path: String,
+    data: Vec<u8>,
+    pos: u64,
+}
+
+/// The files a guest has open, saved in a snapshot.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct FsState {
+    open: Vec<OpenFileState>,
+    next_fd: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct OpenFileState {
+    fd: u32,
+    path: String,
+    pos: u64,
+}
+
+/// Files which the guest may read, from a directory on the host and files
+/// given in memory, along with a record of the bytes the guest read.
+#[derive(Default)]
+pub(crate) struct FileSystem {
+    root: Option<PathBuf>,
+    files: BTreeMap<String, Vec<u8>>,
+    open: HashMap<u32, OpenFile>,
+    next_fd: u32,
+    reads: BTreeMap<String, Vec<Range<u64>>>,
+}
+
+// Turns a guest path into a key relative to the root of the filesystem,
+// without leading or trailing slashes.  Returns None for paths which would
+// escape the root.
+fn normalize(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn status(status: u32) -> Vec<u8> {
+    status.to_le_bytes().to_vec()
+}
+
+// The status to reply with when accessing the host's filesystem fails.
+fn io_status(err: &io::Error) -> u32 {
+    match err.kind() {
+        ErrorKind::NotFound => STATUS_NOT_FOUND,
+        _ => STATUS_IO_ERROR,
+    }
+}
+
+fn push_u32(reply: &mut Vec<u8>, value: u32) {
+    reply.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u64(reply: &mut Vec<u8>, value: u64) {
+    push_u32(reply, value as u32);
+    push_u32(reply, (value >> 32) as u32);
+}
+
+// Parses the fixed arguments of a request, returning them along with the
+// path which follows them.
+fn parse_request<const N: usize>(args: &[u8]) -> Result<([u32; N], &[u8])> {
+    if args.len() < N * 4 {
+        bail!("Truncated filesystem request");
+    }
+    let (words, rest) = args.split_at(N * 4);
+    let mut values = [0; N];
+    for (value, word) in values.iter_mut().zip(words.chunks_exact(4)) {
+        *value = u32::from_le_bytes(word.try_into().unwrap());
+    }
+    Ok((values, rest))
+}
+
+impl FileSystem {
+    pub fn set_root(&mut self, root: PathBuf) {
+        self.root = Some(root);
+    }
+
+    pub fn add_file(&mut self, path: &str, contents: Vec<u8>) -> Result<()> {
+        let key = normalize(path)
+            .filter(|key| !key.is_empty())
+            .with_context(|| format!("Invalid guest file path {path:?}"))?;
+        self.files.insert(key, contents);
+        Ok(())
+    }
+
+    /// Returns the files the guest has open, and where it is in each.
+    pub fn save(&self) -> FsState {
+        let mut open: Vec<_> = self
+            .open
+            .iter()
+            .map(|(fd, file)| OpenFileState {
+                fd: *fd,
+                path: file.path.clone(),
+                pos: file.pos,
+            })
+            .collect();
+        open.sort_by_key(|file| file.fd);
+        FsState {
+            open,
+            next_fd: self.next_fd,
+        }
+    }
+
+    /// Reopens the files saved with [FileSystem::save], which must still be
+    /// available to the guest.
+    pub fn restore(&mut self, state: &FsState) -> Result<()> {
+        let mut open = HashMap::new();
+        for file in state.open.iter() {
+            let data = match self.lookup(file.path.as_bytes()) {
+                Ok(Entry::Memory(key)) => self.files[&key].clone(),
+                Ok(Entry::Host(host_path, _)) => fs::read(&host_path)
+                    .with_context(|| format!("Failed to reopen {}", host_path.display()))?,
+                _ => bail!("File {} is no longer available", file.path),
+            };
+            open.insert(
+                file.fd,
+                OpenFile {
+                    path: file.path.clone(),
+                    data,
+                    pos: file.pos,
+                },
+            );
+        }
+        self.open = open;
+        self.next_fd = state.next_fd;
+        Ok(())
+    }
+
+    /// Handles a request sent on the filesystem channel.
+    pub fn request(&mut self, request: &[u8]) -> Result<Vec<u8>> {
+        let ([op], args) = parse_request(request)?;
+        match op {
+            OP_OPEN => self.open(args),
+            OP_SEEK => {
+                let ([fd, lo, hi], _) = parse_request(args)?;
+                Ok(match self.open.get_mut(&fd) {
+                    Some(file) => {
+                        file.pos = (hi as u64) << 32 | lo as u64;
+                        status(STATUS_OK)
+                    }
+                    None => status(STATUS_INVALID),
+                })
+            }
+            OP_CLOSE => {
+                let ([fd], _) = parse_request(args)?;
+                self.open.remove(&fd);
+                Ok(Vec::new())
+            }
+            OP_METADATA => {
+                let (kind, len) = match self.lookup(args) {
+                    Ok(Entry::Memory(key)) => (KIND_FILE, self.files[&key].len() as u64),
+                    Ok(Entry::Host(_, len)) => (KIND_FILE, len),
+                    Ok(Entry::Dir(_)) => (KIND_DIR, 0),
+                    Err(err) => return Ok(status(err)),
+                };
+                let mut reply = status(STATUS_OK);
+                push_u32(&mut reply, kind);
+                push_u64(&mut reply, len);
+                Ok(reply)
+            }
+            OP_READ_DIR => Ok(match self.lookup(args) {
+                Ok(Entry::Dir(entries)) => {
+                    let mut reply = status(STATUS_OK);
+                    push_u32(&mut reply, entries.len() as u32);
+                    for (name, kind) in entries {
+                        push_u32(&mut reply, kind);
+                        push_u32(&mut reply, name.len() as u32);
+                        reply.extend_from_slice(name.as_bytes());
+                        reply.resize(reply.len() + (4 - name.len() % 4) % 4, 0);
+                    }
+                    reply
+                }
+                Ok(_) => status(STATUS_NOT_A_DIRECTORY),
+                Err(err) => status(err),
+            }),
+            _ => bail!("Unknown filesystem operation {op}"),
+        }
+    }
+
+    /// Reads up to `nbytes` from the current position of an open file.
+    pub fn read(&mut self, fd: u32, nbytes: usize) -> Result<Vec<u8>> {
+        let file = match self.open.get_mut(&fd) {
+            Some(file) => file,
+            None => bail!("Unknown file descriptor: {fd}"),
+        };
+        let len = file.data.len() as u64;
+        let start = file.pos.min(len);
+        let end = start.saturating_add(nbytes as u64).min(len);
+        file.pos = end;
+        if start < end {
+            self.reads
+                .entry(file.path.clone())
+                .or_default()
+                .push(start..end);
+        }
+        Ok(file.data[start as usize..end as usize].to_vec())
+    }
+
+    /// Returns the ranges of bytes read from each file, merged and sorted.
+    pub fn reads(&self) -> BTreeMap<String, Vec<Range<u64>>> {
+        self.reads
+            .iter()
+            .map(|(path, ranges)| {
+                let mut ranges = ranges.clone();
+                ranges.sort_by_key(|range| range.start);
+                let mut merged: Vec<Range<u64>> = Vec::new();
+                for range in ranges {
+                    match merged.last_mut() {
+                        Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                        _ => merged.push(range),
+                    }
+                }
+                (path.clone(), merged)
+            })
+            .collect()
+    }
+
+    fn open(&mut self, path: &[u8]) -> Result<Vec<u8>> {
+        let key = match normalize(&String::from_utf8_lossy(path)) {
+            Some(key) => key,
+            None => return Ok(status(STATUS_INVALID)),
+        };
+        let data = match self.lookup(path) {
+            Ok(Entry::Memory(key)) => self.files[&key].clone(),
+            Ok(Entry::Host(host_path, _)) => match fs::read(host_path) {
+                Ok(data) => data,
+                Err(err) => return Ok(status(io_status(&err))),
+            },
+            Ok(Entry::Dir(_)) => return Ok(status(STATUS_IS_A_DIRECTORY)),
+            Err(err) => return Ok(status(err)),
+        };
+        let fd = FIRST_FD + self.next_fd;
+        self.next_fd += 1;
+        let mut reply = status(STATUS_OK);
+        push_u32(&mut reply, fd);
+        push_u64(&mut reply, data.len() as u64);
+        self.open.insert(
+            fd,
+            OpenFile {
+                path: key,
+                data,
+                pos: 0,
+            },
+        );
+        Ok(reply)
+    }
+
+    // Finds the file or directory at the given guest path, or the status to
+    // reply with if there isn't one.  Files given in memory take precedence
+    // over files under the root directory.
+    fn lookup(&self, path: &[u8]) -> std::result::Result<Entry, u32> {
+        let key = normalize(&String::from_utf8_lossy(path)).ok_or(STATUS_INVALID)?;
+        if self.files.contains_key(&key) {
+            return Ok(Entry::Memory(key));
+        }
+
+        let mut entries = BTreeMap::new();
+        let mut is_dir = key.is_empty();
+        let prefix = if key.is_empty() {
+            String::new()
+        } else {
+            format!("{key}/")
+        };
+        let mut dirs = BTreeSet::new();
+        for name in self.files.keys() {
+            if let Some(rest) = name.strip_prefix(&prefix) {
+                is_dir = true;
+                match rest.split_once('/') {
+                    Some((dir, _)) => {
+                        dirs.insert(dir.to_string());
+                    }
+                    None => {
+                        entries.insert(rest.to_string(), KIND_FILE);
+                    }
+                }
+            }
+        }
+        for dir in dirs {
+            entries.insert(dir, KIND_DIR);
+        }
+
+        if let Some(ref root) = self.root {
+            match lookup_host(root, &key) {
+                Ok(Some(Entry::Host(..))) if is_dir => {}
+                Ok(Some(Entry::Dir(host_entries))) => {
+                    is_dir = true;
+                    for (name, kind) in host_entries {
+                        entries.entry(name).or_insert(kind);
+                    }
+                }
+                Ok(Some(entry)) => return Ok(entry),
+                Ok(None) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(io_status(&err)),
+            }
+        }
+        if is_dir {
+            Ok(Entry::Dir(entries))
+        } else {
+            Err(STATUS_NOT_FOUND)
+        }
+    }
+}
+
+// Finds the file or directory for the given key under the root directory.
+// Symbolic links are followed, but only while they stay under the root.
+fn lookup_host(root: &Path, key: &str) -> io::Result<Option<Entry>> {
+    let root = root.canonicalize()?;
+    let host_path = root.join(key).canonicalize()?;
+    if !host_path.starts_with(&root) {
+        return Ok(None);
+    }
+    let metadata = fs::metadata(&host_path)?;
+    if metadata.is_file() {
+        return Ok(Some(Entry::Host(host_path, metadata.len())));
+    }
+    if !metadata.is_dir() {
+        return Ok(None);
+    }
+    let mut entries = BTreeMap::new();
+    for entry in fs::read_dir(&host_path)? {
+        let entry = entry?;
+        // Follow symbolic links, so they're listed as what they point to.
+        let kind = if fs::metadata(entry.path()).map_or(false, |metadata| metadata.is_dir()) {
+            KIND_DIR
+        } else {
+            KIND_FILE
+        };
+        entries.insert(entry.file_name().to_string_lossy().into_owned(), kind);
+    }
+    Ok(Some(Entry::Dir(entries)))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use risc0_zkvm_platform::io::fs::*;
+
+    use super::FileSystem;
+
+    // Sends a request for a path, returning the status and the rest of the
+    // reply.
+    fn request(fs: &mut FileSystem, op: u32, path: &str) -> (u32, Vec<u8>) {
+        let mut buf = op.to_le_bytes().to_vec();
+        buf.extend_from_slice(path.as_bytes());
+        let reply = fs.request(&buf).unwrap();
+        let (status, rest) = reply.split_at(4);
+        (
+            u32::from_le_bytes(status.try_into().unwrap()),
+            rest.to_vec(),
+        )
+    }
+
+    #[test]
+    fn symlinks() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("inside.txt"), "inside").unwrap();
+        symlink("inside.txt", root.path().join("alias.txt")).unwrap();
+        symlink(
+            outside.path().join("secret.txt"),
+            root.path().join("escape.txt"),
+        )
+        .unwrap();
+        symlink(outside.path(), root.path().join("escape")).unwrap();
+
+        let mut fs = FileSystem::default();
+        fs.set_root(root.path().to_path_buf());
+
+        // Links within the root are followed.
+        let (status, reply) = request(&mut fs, OP_METADATA, "alias.txt");
+        assert_eq!(status, STATUS_OK);
+        assert_eq!(reply, [0, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(request(&mut fs, OP_OPEN, "alias.txt").0, STATUS_OK);
+
+        // Links out of the root aren't.
+        for path in ["escape.txt", "escape/secret.txt"] {
+            assert_eq!(request(&mut fs, OP_METADATA, path).0, STATUS_NOT_FOUND);
+            assert_eq!(request(&mut fs, OP_OPEN, path).0, STATUS_NOT_FOUND);
+        }
+        assert_eq!(request(&mut fs, OP_READ_DIR, "escape").0, STATUS_NOT_FOUND);
+    }
+}
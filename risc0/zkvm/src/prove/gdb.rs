@@ -0,0 +1,339 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A stub for the GDB remote serial protocol, which lets a debugger attach to
+//! a guest running in the executor.
+//!
+//! The stub supports reading registers, reading and writing memory,
+//! breakpoints, single-stepping and continuing.  Execution stops before the
+//! first instruction of the guest to wait for the debugger.
+
+use std::{
+    collections::BTreeSet,
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+use anyhow::{bail, Result};
+use log::debug;
+
+// Describes the target to the debugger, so it knows the guest is a 32-bit
+// RISC-V processor even without the ELF.
+const TARGET_XML: &str = concat!(
+    r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd">"#,
+    r#"<target version="1.0"><architecture>riscv:rv32</architecture></target>"#
+);
+
+// The registers reported to the debugger are x0-x31, followed by the pc.
+const NUM_REGS: usize = 33;
+const REG_PC: usize = 32;
+
+/// A view of the guest's registers and memory for the debugger.
+pub trait GdbTarget {
+    fn load_register(&self, reg: usize) -> Result<u32>;
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>>;
+    fn store_region(&mut self, addr: u32, data: &[u8]) -> Result<()>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    // Stop before the next instruction.
+    Step,
+    // Run until a breakpoint is hit.
+    Continue,
+    // The debugger has gone away, so run without stopping.
+    Detached,
+}
+
+pub struct GdbStub {
+    stream: TcpStream,
+    breakpoints: BTreeSet<u32>,
+    mode: Mode,
+    // Whether the debugger has seen the initial stop.  Later stops need to be
+    // reported to the debugger.
+    started: bool,
+    // Whether the debugger may write guest memory.  Such writes bypass the
+    // circuit, so they are refused while sealing.
+    writable: bool,
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            started: false,
+            writable: false,
+        }
+    }
+
+    /// Sets whether the debugger may write guest memory.
+    pub fn set_writable(&mut self, writable: bool) {
+        self.writable = writable;
+    }
+
+    /// Called before each instruction is executed; blocks while the debugger
+    /// has the guest stopped.
+    pub fn on_instruction(&mut self, pc: u32, target: &mut dyn GdbTarget) -> Result<()> {
+        match self.mode {
+            Mode::Detached => return Ok(()),
+            Mode::Continue if !self.breakpoints.contains(&pc) => return Ok(()),
+            _ => {}
+        }
+        if self.started {
+            self.send_packet("S05")?;
+        }
+        self.started = true;
+        self.serve(pc, target)
+    }
+
+    /// Called once the guest halts.
+    pub fn on_halt(&mut self) -> Result<()> {
+        if self.mode != Mode::Detached {
+            self.send_packet("W00")?;
+            self.mode = Mode::Detached;
+        }
+        Ok(())
+    }
+
+    // Handles packets from the debugger until it resumes execution.
+    fn serve(&mut self, pc: u32, target: &mut dyn GdbTarget) -> Result<()> {
+        let load_register = |target: &dyn GdbTarget, reg| match reg {
+            REG_PC => Ok(pc),
+            _ => target.load_register(reg),
+        };
+        let encode_register = |target: &dyn GdbTarget, reg| {
+            load_register(target, reg).map(|value: u32| encode_hex(&value.to_le_bytes()))
+        };
+        loop {
+            let packet = match self.read_packet()? {
+                Some(packet) => packet,
+                None => {
+                    debug!("GDB: connection closed");
+                    self.mode = Mode::Detached;
+                    return Ok(());
+                }
+            };
+            debug!("GDB> {packet}");
+            if packet.is_empty() {
+                self.send_packet("")?;
+                continue;
+            }
+            // The packet may hold arbitrary bytes, so split after the first
+            // character rather than the first byte.
+            let cmd_len = packet.chars().next().map_or(0, char::len_utf8);
+            let (cmd, args) = packet.split_at(cmd_len);
+            let reply = match cmd {
+                "?" => "S05".into(),
+                "g" => (0..NUM_REGS)
+                    .map(|reg| encode_register(target, reg))
+                    .collect::<Result<String>>()
+                    .unwrap_or_else(|_| "E01".into()),
+                "p" => match usize::from_str_radix(args, 16) {
+                    Ok(reg) if reg < NUM_REGS => {
+                        encode_register(target, reg).unwrap_or_else(|_| "E01".into())
+                    }
+                    _ => "E01".into(),
+                },
+                "m" => parse_addr_len(args)
+                    .and_then(|(addr, len)| target.load_region(addr, len).ok())
+                    .map_or_else(|| "E01".into(), |data| encode_hex(&data)),
+                "M" if !self.writable => "E01".into(),
+                "M" => {
+                    let stored = args.split_once(':').and_then(|(addr_len, data)| {
+                        let (addr, len) = parse_addr_len(addr_len)?;
+                        let data = decode_hex(data).filter(|data| data.len() == len as usize)?;
+                        target.store_region(addr, &data).ok()
+                    });
+                    match stored {
+                        Some(()) => "OK".into(),
+                        None => "E01".into(),
+                    }
+                }
+                "Z" | "z" => self.breakpoint(cmd == "Z", args),
+                "s" => {
+                    self.mode = Mode::Step;
+                    return Ok(());
+                }
+                "c" => {
+                    self.mode = Mode::Continue;
+                    return Ok(());
+                }
+                "D" => {
+                    self.send_packet("OK")?;
+                    self.mode = Mode::Detached;
+                    return Ok(());
+                }
+                "k" => bail!("Execution killed by debugger"),
+                "H" | "T" => "OK".into(),
+                "q" => query(args),
+                _ => String::new(),
+            };
+            self.send_packet(&reply)?;
+        }
+    }
+
+    // Inserts or removes a software or hardware breakpoint.  Watchpoints are
+    // not supported.
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut parts = args.split(',');
+        let kind = parts.next();
+        let addr = parts
+            .next()
+            .and_then(|addr| u32::from_str_radix(addr, 16).ok());
+        match (kind, addr) {
+            (Some("0" | "1"), Some(addr)) => {
+                if insert {
+                    self.breakpoints.insert(addr);
+                } else {
+                    self.breakpoints.remove(&addr);
+                }
+                "OK".into()
+            }
+            (Some("0" | "1"), None) => "E01".into(),
+            _ => String::new(),
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    // Reads the next packet from the debugger, or returns None if the
+    // connection has been closed.
+    fn read_packet(&mut self) -> Result<Option<String>> {
+        loop {
+            // Skip acks and interrupts until the start of a packet.
+            loop {
+                match self.read_byte()? {
+                    Some(b'$') => break,
+                    Some(_) => {}
+                    None => return Ok(None),
+                }
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let checksum = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+            if checksum == Some(compute_checksum(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            // Ask the debugger to resend a corrupted packet.
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> Result<()> {
+        debug!("GDB< {data}");
+        let checksum = compute_checksum(data.as_bytes());
+        loop {
+            write!(self.stream, "${data}#{checksum:02x}")?;
+            self.stream.flush()?;
+            // Resend until the debugger acknowledges the packet.
+            if self.read_byte()? != Some(b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Handles general query packets.
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        return "PacketSize=1000;qXfer:features:read+".into();
+    }
+    if let Some(offset_len) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        return match parse_addr_len(offset_len) {
+            Some((offset, len)) => {
+                let start = (offset as usize).min(TARGET_XML.len());
+                let end = (start + len as usize).min(TARGET_XML.len());
+                let marker = if end == TARGET_XML.len() { "l" } else { "m" };
+                format!("{marker}{}", &TARGET_XML[start..end])
+            }
+            None => "E01".into(),
+        };
+    }
+    match args {
+        "Attached" => "1".into(),
+        "C" => "QC1".into(),
+        "fThreadInfo" => "m1".into(),
+        "sThreadInfo" => "l".into(),
+        _ => String::new(),
+    }
+}
+
+// Parses an "addr,length" pair of hex numbers.
+fn parse_addr_len(args: &str) -> Option<(u32, u32)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u32::from_str_radix(addr, 16).ok()?,
+        u32::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn compute_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    if data.len() % 2 != 0 {
+        return None;
+    }
+    (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(data.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_hex, encode_hex, query};
+
+    #[test]
+    fn hex_round_trip() {
+        let data = [0x00, 0x12, 0xab, 0xff];
+        assert_eq!(encode_hex(&data), "0012abff");
+        assert_eq!(decode_hex("0012abff").unwrap(), data);
+        assert_eq!(decode_hex("0012a"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+
+    #[test]
+    fn target_xml_chunks() {
+        let first = query("Xfer:features:read:target.xml:0,10");
+        assert!(first.starts_with('m'));
+        assert_eq!(first.len(), 0x10 + 1);
+        let last = query("Xfer:features:read:target.xml:10,1000");
+        assert!(last.starts_with('l'));
+    }
+}
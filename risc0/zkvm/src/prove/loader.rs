@@ -322,7 +322,7 @@ impl Loader {
     const INIT_CYCLES: usize = 1;
     const RESET_CYCLES: usize = 1;
     const SETUP_CYCLES: usize = setup_count(SETUP_STEP_REGS);
-    const FINI_CYCLES: usize = 2;
+    pub const FINI_CYCLES: usize = 2;
 
     pub fn new(image: &BTreeMap<u32, u32>) -> Self {
         let mut regs: BTreeMap<u32, u32> = BTreeMap::new();
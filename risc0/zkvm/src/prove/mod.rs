@@ -12,41 +12,129 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "profiler")]
+pub mod coverage;
 pub(crate) mod elf;
+mod emu;
+mod error;
 mod exec;
+mod fs;
+#[cfg(feature = "gdb")]
+mod gdb;
 pub(crate) mod loader;
 mod plonk;
+mod pool;
 #[cfg(feature = "profiler")]
 pub mod profiler;
+mod stats;
+mod syscall;
+pub mod trace;
 
-use std::{collections::HashMap, env, fmt::Debug, io::Write, rc::Rc};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    env,
+    fmt::Debug,
+    io::{self, Read, Write},
+    ops::Range,
+    path::Path,
+    rc::Rc,
+    time::Instant,
+};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use rand::RngCore;
+pub use risc0_zkp::prove::{ProveEvent, ProvePhase};
 use risc0_zkp::{
     field::baby_bear::{BabyBearElem, BabyBearExtElem},
     hal::{EvalCheck, Hal},
     prove::adapter::ProveAdapter,
+    MAX_CYCLES_PO2,
 };
 use risc0_zkvm_platform::{
-    io::{SENDRECV_CHANNEL_INITIAL_INPUT, SENDRECV_CHANNEL_STDERR, SENDRECV_CHANNEL_STDOUT},
-    memory::MEM_SIZE,
+    io::{
+        FILENO_STDIN, SENDRECV_CHANNEL_FS, SENDRECV_CHANNEL_INITIAL_INPUT, SENDRECV_CHANNEL_STDERR,
+        SENDRECV_CHANNEL_STDOUT, SENDRECV_CHANNEL_ZEROIO_INPUT,
+    },
+    memory::{INPUT, MEM_SIZE},
+    syscall::nr::SYS_USER_BASE,
 };
 
 use self::elf::Program;
-use crate::{method_id::MethodId, receipt::Receipt, sha::sha, CIRCUIT};
+pub use self::{
+    emu::Snapshot,
+    error::{ExecutionError, MemoryFaultKind},
+    pool::{ProveHandle, ProverPool},
+    stats::{MemoryStats, PAGE_SIZE},
+    syscall::{SyscallContext, SyscallHandler},
+};
+use crate::{
+    method_id::MethodId,
+    receipt::{ExitCode, Receipt, SegmentReceipt, SessionReceipt},
+    sha::sha,
+    CIRCUIT,
+};
 
 pub fn insecure_skip_seal() -> bool {
     cfg!(feature = "insecure_skip_seal")
         && env::var("RISC0_INSECURE_SKIP_SEAL").unwrap_or_default() == "1"
 }
 
+/// A handler for a sendrecv channel, registered with
+/// [ProverOpts::with_host_channel].
+///
+/// Unlike the callbacks given to [ProverOpts::with_sendrecv_callback], a
+/// channel may hold mutable state and may fail.
+pub trait HostChannel {
+    /// Handles a buffer sent by the guest on the given channel, returning the
+    /// reply.  Returning an error aborts execution with that error.
+    fn send_recv(&mut self, channel: u32, buf: &[u8]) -> Result<Vec<u8>>;
+}
+
+impl<T: HostChannel + ?Sized> HostChannel for &mut T {
+    fn send_recv(&mut self, channel: u32, buf: &[u8]) -> Result<Vec<u8>> {
+        (**self).send_recv(channel, buf)
+    }
+}
+
+// Adapts a callback given to ProverOpts::with_sendrecv_callback.
+struct SendRecvCallback<F>(F);
+
+impl<F: Fn(u32, &[u8]) -> Vec<u8>> HostChannel for SendRecvCallback<F> {
+    fn send_recv(&mut self, channel: u32, buf: &[u8]) -> Result<Vec<u8>> {
+        Ok((self.0)(channel, buf))
+    }
+}
+
 /// Options available to modify the prover's behavior.
 pub struct ProverOpts<'a> {
     pub(crate) skip_seal: bool,
 
-    pub(crate) sendrecv_callbacks: HashMap<u32, Box<dyn Fn(u32, &[u8]) -> Vec<u8> + 'a + Sync>>,
+    pub(crate) host_channels: HashMap<u32, Box<dyn HostChannel + 'a>>,
 
     pub(crate) trace_callback: Option<Box<dyn FnMut(TraceEvent) -> Result<()> + 'a>>,
+
+    pub(crate) max_po2: usize,
+
+    pub(crate) max_cycles: Option<usize>,
+
+    pub(crate) segment_cycles: Option<usize>,
+
+    pub(crate) syscall_handlers: HashMap<u32, Box<dyn SyscallHandler + 'a>>,
+
+    #[cfg(feature = "gdb")]
+    pub(crate) gdb_stub: Option<gdb::GdbStub>,
+
+    pub(crate) trace_writer: Option<trace::TraceWriter>,
+
+    pub(crate) replay: Option<trace::Replay>,
+
+    pub(crate) progress: Option<Box<dyn FnMut(ProveEvent) + 'a>>,
+
+    pub(crate) rng_seed: Option<u64>,
+
+    pub(crate) rng_source: Option<Box<dyn RngCore + 'a>>,
+
+    pub(crate) fs: fs::FileSystem,
 }
 
 impl<'a> ProverOpts<'a> {
@@ -66,12 +154,21 @@ impl<'a> ProverOpts<'a> {
         channel_id: u32,
         callback: impl Fn(u32, &[u8]) -> Vec<u8> + 'a + Sync,
     ) -> Self {
-        self.sendrecv_callbacks
-            .insert(channel_id, Box::new(callback));
+        self.host_channels
+            .insert(channel_id, Box::new(SendRecvCallback(callback)));
         self
     }
 
-    /// Add a callback handler for raw trace messages.
+    /// Add a handler for the given sendrecv channel.  The guest can send to
+    /// the channel by invoking `risc0_zkvm::guest::env::send_recv`.
+    pub fn with_host_channel(mut self, channel_id: u32, channel: impl HostChannel + 'a) -> Self {
+        self.host_channels.insert(channel_id, Box::new(channel));
+        self
+    }
+
+    /// Add a callback handler for raw trace messages.  Only
+    /// [Prover::run] traces the guest; [Prover::execute] emulates it without
+    /// emitting any [TraceEvent]s.
     pub fn with_trace_callback(
         mut self,
         callback: impl FnMut(TraceEvent) -> Result<()> + 'a,
@@ -80,14 +177,166 @@ impl<'a> ProverOpts<'a> {
         self.trace_callback = Some(Box::new(callback));
         self
     }
+
+    /// Limit the execution trace to at most `2^max_po2` cycles.  Guests
+    /// which need a larger trace fail with
+    /// [ExecutionError::CycleLimitExceeded].
+    pub fn with_max_po2(self, max_po2: usize) -> Self {
+        assert!(
+            max_po2 <= MAX_CYCLES_PO2,
+            "max_po2 may not exceed {MAX_CYCLES_PO2}"
+        );
+        Self { max_po2, ..self }
+    }
+
+    /// Add a handler for a custom syscall, which the guest can invoke with
+    /// `risc0_zkvm_platform::syscall::sys_user`.  The syscall number must be
+    /// at least [SYS_USER_BASE].
+    pub fn with_syscall(mut self, nr: u32, handler: impl SyscallHandler + 'a) -> Self {
+        assert!(
+            nr >= SYS_USER_BASE,
+            "Syscall {nr} is reserved, custom syscalls start at {SYS_USER_BASE}"
+        );
+        assert!(
+            !self.syscall_handlers.contains_key(&nr),
+            "Duplicate handler for syscall {nr}"
+        );
+        self.syscall_handlers.insert(nr, Box::new(handler));
+        self
+    }
+
+    /// Stop execution with [ExecutionError::CycleBudgetExceeded] once the
+    /// guest has run for `max_cycles` cycles, including the cycles needed to
+    /// load the program.
+    pub fn with_max_cycles(self, max_cycles: usize) -> Self {
+        Self {
+            max_cycles: Some(max_cycles),
+            ..self
+        }
+    }
+
+    /// Let [Prover::run_session] end a segment at the first checkpoint the
+    /// guest reaches, with `risc0_zkvm::guest::env::checkpoint`, once the
+    /// segment has run for `segment_cycles` cycles.  Each segment is still
+    /// limited to a trace of `2^max_po2` cycles, so the guest must reach a
+    /// checkpoint before then.
+    pub fn with_segment_limit(self, segment_cycles: usize) -> Self {
+        Self {
+            segment_cycles: Some(segment_cycles),
+            ..self
+        }
+    }
+
+    /// Record a trace of the guest's execution to the given file, including
+    /// the replies to its syscalls, so the execution can be reproduced later
+    /// with [trace::Replay].  The file is complete once the guest halts, and
+    /// failures to write it are returned by [Prover::run] and
+    /// [Prover::execute].
+    pub fn with_trace_file(self, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            trace_writer: Some(trace::TraceWriter::create(path.as_ref())?),
+            ..self
+        })
+    }
+
+    /// Serve the guest's syscalls from a recorded trace instead of the host
+    /// channels, syscall handlers and inputs given to the [Prover].
+    /// Execution fails if the guest does not execute exactly as it did when
+    /// the trace was recorded.
+    pub fn with_replay(self, replay: trace::Replay) -> Self {
+        Self {
+            replay: Some(replay),
+            ..self
+        }
+    }
+
+    /// Add a callback which is told about the progress of [Prover::run]:
+    /// when each phase of proving starts and finishes, the size of the
+    /// execution trace, and each round of FRI.  Without a seal, only the
+    /// execution phase is reported.
+    pub fn with_progress(mut self, callback: impl FnMut(ProveEvent) + 'a) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Seed the RNG which generates the random padding of the execution
+    /// trace, so that proving the same execution twice produces
+    /// byte-for-byte identical seals.  This is useful for golden-file tests
+    /// and debugging.
+    ///
+    /// The padding is what makes the seal zero-knowledge, and anyone who
+    /// knows the seed can regenerate it, so this must never be used for
+    /// proofs about private data.
+    pub fn with_rng_seed(self, seed: u64) -> Self {
+        Self {
+            rng_seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Supply the random bytes the guest requests with
+    /// `risc0_zkvm_platform::syscall::sys_random`, e.g. through `getrandom`,
+    /// from the given RNG instead of the host's thread-local RNG.  This lets
+    /// tests give the guest deterministic "random" data.
+    pub fn with_rng_source(mut self, rng: impl RngCore + 'a) -> Self {
+        self.rng_source = Some(Box::new(rng));
+        self
+    }
+
+    /// Let the guest read the files under the given directory with
+    /// `risc0_zkvm::guest::fs`.  Guest paths are relative to this directory,
+    /// and may not refer to anything outside it, even through symbolic
+    /// links.
+    pub fn with_fs_root(mut self, root: impl AsRef<Path>) -> Self {
+        self.fs.set_root(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Let the guest read a file with the given contents at the given path
+    /// with `risc0_zkvm::guest::fs`.  This takes precedence over a file at
+    /// the same path under the directory given to
+    /// [ProverOpts::with_fs_root].
+    pub fn with_file(mut self, path: &str, contents: impl Into<Vec<u8>>) -> Result<Self> {
+        self.fs.add_file(path, contents.into())?;
+        Ok(self)
+    }
+
+    /// Let a debugger connected to `stream` control the guest using the GDB
+    /// remote serial protocol, e.g. with `target remote` in a riscv32 gdb.
+    /// The guest stops before its first instruction to wait for the
+    /// debugger.
+    ///
+    /// The debugger is driven by the circuit executor, so this only applies
+    /// to [Prover::run].  Writes to guest memory from the debugger would
+    /// invalidate the seal, so they are refused unless combined with
+    /// [ProverOpts::with_skip_seal].
+    #[cfg(feature = "gdb")]
+    pub fn with_gdb(self, stream: std::net::TcpStream) -> Self {
+        Self {
+            gdb_stub: Some(gdb::GdbStub::new(stream)),
+            ..self
+        }
+    }
 }
 
 impl<'a> Default for ProverOpts<'a> {
     fn default() -> ProverOpts<'a> {
         ProverOpts {
             skip_seal: false,
-            sendrecv_callbacks: HashMap::new(),
+            host_channels: HashMap::new(),
             trace_callback: None,
+            max_po2: MAX_CYCLES_PO2,
+            max_cycles: None,
+            segment_cycles: None,
+            syscall_handlers: HashMap::new(),
+            #[cfg(feature = "gdb")]
+            gdb_stub: None,
+            trace_writer: None,
+            replay: None,
+            progress: None,
+            rng_seed: None,
+            rng_source: None,
+            fs: fs::FileSystem::default(),
         }
     }
 }
@@ -96,6 +345,12 @@ pub struct Prover<'a> {
     elf: Program,
     inner: ProverImpl<'a>,
     method_id: MethodId,
+    // State of a paused execute-only run, if any.
+    paused: Option<Snapshot>,
+    memory_stats: MemoryStats,
+    /// The number of cycles used by the last run, summed over its segments
+    /// for [Prover::run_session], or estimated by the last execution,
+    /// excluding the padding up to the size of the trace.
     pub cycles: usize,
 }
 
@@ -158,6 +413,8 @@ impl<'a> Prover<'a> {
             elf: Program::load_elf(&elf, MEM_SIZE as u32)?,
             inner: ProverImpl::new(opts),
             method_id: method_id.into(),
+            paused: None,
+            memory_stats: MemoryStats::default(),
             cycles: 0,
         })
     }
@@ -172,10 +429,154 @@ impl<'a> Prover<'a> {
             .extend_from_slice(bytemuck::cast_slice(slice));
     }
 
+    /// Add a value for the guest to read with
+    /// `risc0_zkvm::guest::env::read_zeroio`, which accesses it in place
+    /// instead of copying it.  Values are read in the order they were added,
+    /// and each is given to the guest once.
+    pub fn add_input_zeroio<T: risc0_zeroio::Serialize>(&mut self, val: &T) -> Result<()> {
+        let words = risc0_zeroio::to_vec(val).map_err(|err| anyhow!("{err}"))?;
+        self.inner
+            .zeroio_input
+            .push_back(bytemuck::cast_slice(&words).to_vec());
+        Ok(())
+    }
+
+    /// Add a reader for the guest's standard input, which the guest reads in
+    /// chunks using `risc0_zkvm::guest::env::stdin`.  If more than one reader
+    /// is added, they are read in the order they were added.
+    pub fn add_input_reader(&mut self, reader: impl Read + 'a) {
+        self.inner.stdin = Some(match self.inner.stdin.take() {
+            Some(stdin) => Box::new(stdin.chain(reader)),
+            None => Box::new(reader),
+        });
+    }
+
+    /// Add a command-line argument for the guest, which it can read with
+    /// `risc0_zkvm::guest::env::args`.  As with a native program, the first
+    /// argument is conventionally the program's name.
+    pub fn add_arg(&mut self, arg: &str) {
+        self.inner.args.push(arg.to_string());
+    }
+
+    /// Set an environment variable for the guest, which it can read with
+    /// `risc0_zkvm::guest::env::var`.
+    pub fn set_env(&mut self, name: &str, value: &str) {
+        self.inner.env.insert(name.to_string(), value.to_string());
+    }
+
+    /// Returns the ranges of bytes the guest read from each file given with
+    /// [ProverOpts::with_fs_root] or [ProverOpts::with_file], which are
+    /// exactly the parts of the files the guest's execution depended on.
+    /// Files are keyed by their path in the guest, without a leading slash.
+    pub fn file_reads(&self) -> BTreeMap<String, Vec<Range<u64>>> {
+        self.inner.opts.fs.reads()
+    }
+
     pub fn get_output(&self) -> &[u8] {
         &self.inner.output
     }
 
+    /// Returns statistics on the guest's use of memory during the last call
+    /// to [Prover::run] or [Prover::execute].
+    pub fn memory_stats(&self) -> &MemoryStats {
+        &self.memory_stats
+    }
+
+    /// Runs the guest without generating a circuit trace or a seal.
+    ///
+    /// The guest is run by a native RV32IM interpreter which services the same
+    /// syscalls as [Prover::run], but is much faster.  The cycle count and
+    /// po2 reported are estimates of what [Prover::run] would need.  So are
+    /// the cycle counts the guest reads with `env::get_cycle_count`, and a
+    /// journal which depends on them may differ from the one [Prover::run]
+    /// produces.
+    ///
+    /// If a previous execution was paused or a snapshot was supplied with
+    /// [Prover::resume_from], execution continues from there.
+    pub fn execute(&mut self) -> Result<ExecuteResult> {
+        self.execute_until(usize::MAX)
+    }
+
+    /// Like [Prover::execute], but pauses once the estimated number of
+    /// cycles reaches `cycle_limit`.  If the guest was paused before it
+    /// halted, the exit code is [ExitCode::Paused]; the paused state may then
+    /// be saved with [Prover::snapshot] or continued with another call to
+    /// [Prover::execute].
+    #[tracing::instrument(skip_all)]
+    pub fn execute_until(&mut self, cycle_limit: usize) -> Result<ExecuteResult> {
+        let limits = self.inner.limits();
+        let mut emu = match self.paused.take() {
+            Some(snapshot) => emu::Emulator::resume(&self.elf, &snapshot, &mut self.inner)?,
+            None => {
+                self.inner.reset_outputs();
+                emu::Emulator::new(&self.elf, &mut self.inner)?
+            }
+        }
+        .with_limits(limits);
+        let halted = emu.run_until(cycle_limit)?;
+        self.cycles = emu.cycles();
+        self.memory_stats = emu.memory_stats();
+        let po2 = emu.po2();
+        let exit_code = if halted {
+            self.inner.flush_trace()?;
+            self.inner.finish_replay()?;
+            self.inner.exit_code()?
+        } else {
+            self.paused = Some(emu.snapshot());
+            ExitCode::Paused
+        };
+        Ok(ExecuteResult {
+            journal: self.inner.commit.clone(),
+            exit_code,
+            stdout: self.inner.output.clone(),
+            cycles: self.cycles,
+            po2,
+            memory: self.memory_stats.clone(),
+        })
+    }
+
+    /// Returns a snapshot of a paused execute-only run, including the journal
+    /// and stdout written so far.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        let mut snapshot = self
+            .paused
+            .clone()
+            .ok_or_else(|| anyhow!("No paused execution to snapshot"))?;
+        snapshot.journal = self.inner.commit.clone();
+        snapshot.stdout = self.inner.output.clone();
+        snapshot.stdin_pos = self.inner.stdin_pos;
+        snapshot.zeroio_pos = self.inner.zeroio_pos;
+        snapshot.fs = self.inner.opts.fs.save();
+        Ok(snapshot)
+    }
+
+    /// Continues execution from the given snapshot on the next call to
+    /// [Prover::execute].  The snapshot must have been taken from a guest
+    /// running the same ELF.
+    ///
+    /// Snapshots only exist in execute-only mode: the circuit always starts
+    /// from the ELF's entry point, so a resumed execution can't be proven,
+    /// and [Prover::run] fails while one is pending.
+    ///
+    /// The snapshot records how far the guest got through its standard
+    /// input and its zeroio inputs, and the files it has open.  Inputs given
+    /// to this [Prover] are read from the same positions, so they should be
+    /// added in full, as for the original run; open files are reopened, and
+    /// must still be available.  Other host state such as sendrecv callbacks
+    /// is not part of the snapshot, so a snapshot may be resumed with
+    /// different host inputs.
+    pub fn resume_from(&mut self, mut snapshot: Snapshot) -> Result<()> {
+        self.inner.opts.fs.restore(&snapshot.fs)?;
+        self.inner.commit = std::mem::take(&mut snapshot.journal);
+        self.inner.output = std::mem::take(&mut snapshot.stdout);
+        self.inner.stdin_pos = snapshot.stdin_pos;
+        self.inner.stdin_skip = snapshot.stdin_pos;
+        self.inner.zeroio_pos = snapshot.zeroio_pos;
+        self.inner.zeroio_skip = snapshot.zeroio_pos;
+        self.paused = Some(snapshot);
+        Ok(())
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn run(&mut self) -> Result<Receipt> {
         HAL.with(|(hal, eval)| {
@@ -199,24 +600,39 @@ impl<'a> Prover<'a> {
         H: Hal<Elem = BabyBearElem, ExtElem = BabyBearExtElem>,
         E: EvalCheck<H>,
     {
-        let skip_seal = self.inner.opts.skip_seal || insecure_skip_seal();
-
-        let mut executor = exec::RV32Executor::new(&CIRCUIT, &self.elf, &mut self.inner);
-        self.cycles = executor.run()?;
+        // The callback is taken out of the options while proving since the
+        // executor holds on to them.
+        let mut progress = self.inner.opts.progress.take();
+        let result =
+            self.run_with_progress(hal, eval, progress.as_deref_mut().unwrap_or(&mut |_| {}));
+        self.inner.opts.progress = progress;
+        result
+    }
 
-        let mut prover = ProveAdapter::new(&mut executor.executor);
+    fn run_with_progress<H, E>(
+        &mut self,
+        hal: &H,
+        eval: &E,
+        progress: &mut dyn FnMut(ProveEvent),
+    ) -> Result<Receipt>
+    where
+        H: Hal<Elem = BabyBearElem, ExtElem = BabyBearExtElem>,
+        E: EvalCheck<H>,
+    {
+        if self.paused.is_some() {
+            bail!("Can't prove a paused or resumed execution; finish it with Prover::execute");
+        }
+        let skip_seal = self.inner.opts.skip_seal || insecure_skip_seal();
+        self.inner.reset_outputs();
+        let seal = self.prove_segment(hal, eval, progress, skip_seal)?;
 
-        let seal = if skip_seal {
-            risc0_zkp::prove::prove_without_seal(sha(), &mut prover);
-            Vec::new()
-        } else {
-            risc0_zkp::prove::prove(hal, sha(), &mut prover, eval)
-        };
+        self.inner.flush_trace()?;
 
         // Attach the full version of the output journal & construct receipt object
         let receipt = Receipt {
             journal: self.inner.commit.clone(),
             seal,
+            exit_code: self.inner.exit_code()?,
         };
 
         if !skip_seal {
@@ -226,12 +642,200 @@ impl<'a> Prover<'a> {
 
         Ok(receipt)
     }
+
+    /// Proves the guest in segments, each of which ends at the first
+    /// checkpoint the guest reaches once the segment has run for the number
+    /// of cycles given with [ProverOpts::with_segment_limit].  This proves
+    /// guests which run for longer than a single trace can hold, as long as
+    /// they reach a checkpoint often enough; without a segment limit, the
+    /// whole execution is proven in a single segment.
+    ///
+    /// Each segment is executed and proven on its own, so [Prover::cycles]
+    /// is the total over all segments.  Sessions can't be recorded with
+    /// [ProverOpts::with_trace_file] or replayed with
+    /// [ProverOpts::with_replay].
+    #[tracing::instrument(skip_all)]
+    pub fn run_session(&mut self) -> Result<SessionReceipt> {
+        HAL.with(|(hal, eval)| {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "dual")] {
+                    let cpu_hal = risc0_zkp::hal::cpu::BabyBearCpuHal::new();
+                    let cpu_eval = risc0_circuit_rv32im::cpu::CpuEvalCheck::new(&CIRCUIT);
+                    let hal = risc0_zkp::hal::dual::DualHal::new(hal.as_ref(), &cpu_hal);
+                    let eval = risc0_zkp::hal::dual::DualEvalCheck::new(eval, &cpu_eval);
+                    self.run_session_with_hal(&hal, &eval)
+                } else {
+                    self.run_session_with_hal(hal.as_ref(), eval)
+                }
+            }
+        })
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub fn run_session_with_hal<H, E>(&mut self, hal: &H, eval: &E) -> Result<SessionReceipt>
+    where
+        H: Hal<Elem = BabyBearElem, ExtElem = BabyBearExtElem>,
+        E: EvalCheck<H>,
+    {
+        if self.paused.is_some() {
+            bail!("Can't prove a paused or resumed execution; finish it with Prover::execute");
+        }
+        if self.inner.opts.trace_writer.is_some() || self.inner.opts.replay.is_some() {
+            bail!("Sessions can't be recorded or replayed");
+        }
+        let skip_seal = self.inner.opts.skip_seal || insecure_skip_seal();
+        self.inner.reset_outputs();
+        self.inner.pause_after = self.inner.opts.segment_cycles;
+        let mut progress = self.inner.opts.progress.take();
+        let result = self.prove_segments(
+            hal,
+            eval,
+            progress.as_deref_mut().unwrap_or(&mut |_| {}),
+            skip_seal,
+        );
+        self.inner.opts.progress = progress;
+        self.inner.pause_after = None;
+        self.inner.saved_state = None;
+
+        let receipt = SessionReceipt { segments: result? };
+        if !skip_seal {
+            receipt.verify(&self.method_id)?;
+        }
+        Ok(receipt)
+    }
+
+    fn prove_segments<H, E>(
+        &mut self,
+        hal: &H,
+        eval: &E,
+        progress: &mut dyn FnMut(ProveEvent),
+        skip_seal: bool,
+    ) -> Result<Vec<SegmentReceipt>>
+    where
+        H: Hal<Elem = BabyBearElem, ExtElem = BabyBearExtElem>,
+        E: EvalCheck<H>,
+    {
+        let mut segments = Vec::new();
+        let mut cycles = 0;
+        loop {
+            // Each segment commits to its own journal and exit code, while
+            // stdout carries on across segments.
+            let pre_state = self.inner.saved_state.as_ref().map(|state| state.digest);
+            self.inner.commit.clear();
+            self.inner.exit_code = None;
+            let seal = self.prove_segment(hal, eval, progress, skip_seal)?;
+            cycles += self.cycles;
+            let exit_code = self.inner.exit_code()?;
+            let post_state = match exit_code {
+                ExitCode::Paused => match self.inner.saved_state {
+                    Some(ref state) => Some(state.digest),
+                    None => bail!("Guest paused without saving its state"),
+                },
+                _ => None,
+            };
+            segments.push(SegmentReceipt {
+                pre_state,
+                post_state,
+                journal: self.inner.commit.clone(),
+                seal,
+                exit_code,
+            });
+            if post_state.is_none() {
+                self.cycles = cycles;
+                return Ok(segments);
+            }
+        }
+    }
+
+    // Executes the guest from its entry point until it halts or pauses, and
+    // returns the seal.
+    fn prove_segment<H, E>(
+        &mut self,
+        hal: &H,
+        eval: &E,
+        progress: &mut dyn FnMut(ProveEvent),
+        skip_seal: bool,
+    ) -> Result<Vec<u32>>
+    where
+        H: Hal<Elem = BabyBearElem, ExtElem = BabyBearExtElem>,
+        E: EvalCheck<H>,
+    {
+        let rng_seed = self.inner.opts.rng_seed;
+        let limits = self.inner.limits();
+        let mut executor = exec::RV32Executor::new(&CIRCUIT, &self.elf, &mut self.inner, limits);
+        if let Some(seed) = rng_seed {
+            executor.executor.set_rng_seed(seed);
+        }
+        progress(ProveEvent::PhaseStarted {
+            phase: ProvePhase::Execute,
+        });
+        let start = Instant::now();
+        self.cycles = executor.run()?;
+        progress(ProveEvent::PhaseFinished {
+            phase: ProvePhase::Execute,
+            elapsed: start.elapsed(),
+        });
+        self.memory_stats = executor.memory_stats();
+        self.inner.finish_replay()?;
+
+        let mut prover = ProveAdapter::new(&mut executor.executor);
+
+        Ok(if skip_seal {
+            risc0_zkp::prove::prove_without_seal(sha(), &mut prover);
+            Vec::new()
+        } else {
+            risc0_zkp::prove::prove_with_progress(hal, sha(), &mut prover, eval, progress)
+        })
+    }
+}
+
+/// The result of running a guest in execute-only mode with
+/// [Prover::execute].
+#[derive(Clone, Debug)]
+pub struct ExecuteResult {
+    /// The data committed by the guest.
+    pub journal: Vec<u32>,
+    /// How the guest stopped.
+    pub exit_code: ExitCode,
+    /// The data written by the guest to stdout.
+    pub stdout: Vec<u8>,
+    /// The estimated number of cycles needed to prove this execution.
+    pub cycles: usize,
+    /// The estimated size of the execution trace, as a power of 2.
+    pub po2: usize,
+    /// The guest's use of memory.
+    pub memory: MemoryStats,
+}
+
+/// Runs the given ELF with the given initial input in execute-only mode.
+///
+/// See [Prover::execute] for details.
+pub fn execute(elf: &[u8], input: &[u8]) -> Result<ExecuteResult> {
+    let mut prover = Prover::new(elf, MethodId { table: Vec::new() })?;
+    prover.add_input_u8_slice(input);
+    prover.execute()
 }
 
 struct ProverImpl<'a> {
     pub input: Vec<u8>,
+    pub zeroio_input: VecDeque<Vec<u8>>,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub stdin: Option<Box<dyn Read + 'a>>,
+    // The number of bytes of stdin and of zeroio inputs given to the guest,
+    // and the number to skip before giving it more, when resuming from a
+    // snapshot.
+    pub stdin_pos: u64,
+    pub stdin_skip: u64,
+    pub zeroio_pos: usize,
+    pub zeroio_skip: usize,
     pub output: Vec<u8>,
     pub commit: Vec<u32>,
+    pub exit_code: Option<ExitCode>,
+    // The cycle after which the guest pauses at its next checkpoint, if it
+    // should, and the state it saved when it last paused.
+    pub pause_after: Option<usize>,
+    pub saved_state: Option<exec::SavedState>,
     pub opts: ProverOpts<'a>,
 }
 
@@ -239,17 +843,72 @@ impl<'a> ProverImpl<'a> {
     fn new(opts: ProverOpts<'a>) -> Self {
         Self {
             input: Vec::new(),
+            zeroio_input: VecDeque::new(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            stdin: None,
+            stdin_pos: 0,
+            stdin_skip: 0,
+            zeroio_pos: 0,
+            zeroio_skip: 0,
             output: Vec::new(),
             commit: Vec::new(),
+            exit_code: None,
+            pause_after: None,
+            saved_state: None,
             opts,
         }
     }
+
+    fn limits(&self) -> exec::CycleLimits {
+        exec::CycleLimits {
+            max_po2: self.opts.max_po2,
+            max_cycles: self.opts.max_cycles,
+        }
+    }
 }
 
-impl<'a> exec::HostHandler for ProverImpl<'a> {
-    fn on_txrx(&mut self, channel: u32, buf: &[u8]) -> Result<Vec<u8>> {
-        if let Some(cb) = self.opts.sendrecv_callbacks.get(&channel) {
-            return Ok(cb(channel, buf));
+impl<'a> ProverImpl<'a> {
+    // Clears the journal and stdout of a previous run before starting over.
+    fn reset_outputs(&mut self) {
+        self.commit.clear();
+        self.output.clear();
+        self.exit_code = None;
+    }
+
+    // Returns how the guest stopped, which it tells the host when it halts.
+    fn exit_code(&self) -> Result<ExitCode> {
+        self.exit_code
+            .ok_or_else(|| anyhow!("Guest stopped without halting"))
+    }
+
+    // Makes sure everything traced so far is in the trace file.
+    fn flush_trace(&mut self) -> Result<()> {
+        match self.opts.trace_writer {
+            Some(ref mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // Makes sure the guest used up the whole trace it replayed.
+    fn finish_replay(&mut self) -> Result<()> {
+        match self.opts.replay {
+            Some(ref mut replay) => replay.finish(),
+            None => Ok(()),
+        }
+    }
+
+    fn txrx(&mut self, channel: u32, buf: &[u8]) -> Result<Vec<u8>> {
+        if let Some(ref mut replay) = self.opts.replay {
+            if channel == SENDRECV_CHANNEL_STDOUT {
+                self.output.extend(buf);
+            }
+            return replay.txrx(channel);
+        }
+        if let Some(host_channel) = self.opts.host_channels.get_mut(&channel) {
+            return host_channel
+                .send_recv(channel, buf)
+                .with_context(|| format!("Host channel {channel} failed"));
         }
         match channel {
             SENDRECV_CHANNEL_INITIAL_INPUT => {
@@ -263,18 +922,146 @@ impl<'a> exec::HostHandler for ProverImpl<'a> {
             }
             SENDRECV_CHANNEL_STDERR => {
                 log::debug!("SENDRECV_CHANNEL_STDERR: {}", buf.len());
-                std::io::stderr().lock().write_all(buf).unwrap();
+                std::io::stderr().lock().write_all(buf)?;
                 Ok(Vec::new())
             }
+            SENDRECV_CHANNEL_FS => self.opts.fs.request(buf),
+            SENDRECV_CHANNEL_ZEROIO_INPUT => {
+                log::debug!("SENDRECV_CHANNEL_ZEROIO_INPUT");
+                // Skip the values read before the snapshot we resumed from.
+                let skip = std::mem::take(&mut self.zeroio_skip);
+                self.zeroio_input.drain(..skip.min(self.zeroio_input.len()));
+                let value = self
+                    .zeroio_input
+                    .pop_front()
+                    .ok_or_else(|| anyhow!("Guest read more zeroio inputs than were added"))?;
+                self.zeroio_pos += 1;
+                Ok(value)
+            }
             _ => bail!("Unknown channel: {channel}"),
         }
     }
 
+    fn read(&mut self, fd: u32, nbytes: usize) -> Result<Vec<u8>> {
+        if let Some(ref mut replay) = self.opts.replay {
+            return replay.read(fd);
+        }
+        match fd {
+            FILENO_STDIN => {
+                log::debug!("FILENO_STDIN: {nbytes}");
+                // Cap the size of each read so a guest can't make us
+                // allocate an arbitrarily large buffer.
+                let mut buf = vec![0; nbytes.min(INPUT.len_bytes())];
+                let skip = std::mem::take(&mut self.stdin_skip);
+                let nread = match self.stdin {
+                    Some(ref mut stdin) => {
+                        // Pick up where the snapshot we resumed from left off.
+                        let skipped = io::copy(&mut stdin.by_ref().take(skip), &mut io::sink())?;
+                        if skipped != skip {
+                            bail!("Standard input ended before the position in the snapshot");
+                        }
+                        stdin.read(&mut buf)?
+                    }
+                    None if skip > 0 => {
+                        bail!("Standard input ended before the position in the snapshot")
+                    }
+                    None => 0,
+                };
+                self.stdin_pos += nread as u64;
+                buf.truncate(nread);
+                Ok(buf)
+            }
+            _ => self.opts.fs.read(fd, nbytes.min(INPUT.len_bytes())),
+        }
+    }
+
+    // Looks up a command-line argument or environment variable, recording
+    // or replaying it like the other data the host gives the guest.
+    fn env(&mut self, lookup: impl FnOnce(&Self) -> Option<Vec<u8>>) -> Result<Option<Vec<u8>>> {
+        if let Some(ref mut replay) = self.opts.replay {
+            return replay.env();
+        }
+        let value = lookup(self);
+        if let Some(ref mut writer) = self.opts.trace_writer {
+            writer.env(value.as_deref())?;
+        }
+        Ok(value)
+    }
+
+    fn random(&mut self, nbytes: usize) -> Result<Vec<u8>> {
+        if let Some(ref mut replay) = self.opts.replay {
+            return replay.random(nbytes);
+        }
+        let mut buf = vec![0; nbytes];
+        match self.opts.rng_source {
+            Some(ref mut rng) => rng.try_fill_bytes(&mut buf)?,
+            None => rand::thread_rng().try_fill_bytes(&mut buf)?,
+        }
+        Ok(buf)
+    }
+}
+
+impl<'a> exec::HostHandler for ProverImpl<'a> {
+    fn on_txrx(&mut self, channel: u32, buf: &[u8]) -> Result<Vec<u8>> {
+        let reply = self.txrx(channel, buf)?;
+        if let Some(ref mut writer) = self.opts.trace_writer {
+            writer.txrx(channel, &reply)?;
+        }
+        Ok(reply)
+    }
+
+    fn on_read(&mut self, fd: u32, nbytes: usize) -> Result<Vec<u8>> {
+        let data = self.read(fd, nbytes)?;
+        if let Some(ref mut writer) = self.opts.trace_writer {
+            writer.read(fd, &data)?;
+        }
+        Ok(data)
+    }
+
+    fn on_argc(&mut self) -> Result<u32> {
+        let argc = self.env(|inner| Some((inner.args.len() as u32).to_le_bytes().to_vec()))?;
+        let argc: [u8; 4] = argc
+            .unwrap_or_default()
+            .try_into()
+            .map_err(|_| anyhow!("Invalid argument count"))?;
+        Ok(u32::from_le_bytes(argc))
+    }
+
+    fn on_argv(&mut self, index: u32) -> Result<Vec<u8>> {
+        self.env(|inner| {
+            inner
+                .args
+                .get(index as usize)
+                .map(|arg| arg.as_bytes().to_vec())
+        })?
+        .ok_or_else(|| anyhow!("Guest requested argument {index}, which does not exist"))
+    }
+
+    fn on_getenv(&mut self, name: &str) -> Result<Option<Vec<u8>>> {
+        self.env(|inner| inner.env.get(name).map(|value| value.as_bytes().to_vec()))
+    }
+
+    fn on_random(&mut self, nbytes: usize) -> Result<Vec<u8>> {
+        let data = self.random(nbytes)?;
+        if let Some(ref mut writer) = self.opts.trace_writer {
+            writer.random(&data)?;
+        }
+        Ok(data)
+    }
+
     fn is_trace_enabled(&self) -> bool {
         self.opts.trace_callback.is_some()
+            || self.opts.trace_writer.is_some()
+            || self.opts.replay.is_some()
     }
 
     fn on_trace(&mut self, event: TraceEvent) -> Result<()> {
+        if let Some(ref mut replay) = self.opts.replay {
+            replay.check_event(&event)?;
+        }
+        if let Some(ref mut writer) = self.opts.trace_writer {
+            writer.event(&event)?;
+        }
         if let Some(ref mut cb) = self.opts.trace_callback {
             cb(event)
         } else {
@@ -287,8 +1074,59 @@ impl<'a> exec::HostHandler for ProverImpl<'a> {
         Ok(())
     }
 
+    fn on_halt(&mut self, exit_code: ExitCode) -> Result<()> {
+        self.exit_code = Some(exit_code);
+        Ok(())
+    }
+
+    fn on_pause_requested(&mut self, cycle: usize) -> Result<bool> {
+        Ok(self.pause_after.map_or(false, |limit| cycle >= limit))
+    }
+
+    fn on_pause(&mut self, state: exec::SavedState) -> Result<()> {
+        self.saved_state = Some(state);
+        Ok(())
+    }
+
+    fn on_resume(&mut self) -> Result<Option<exec::SavedState>> {
+        Ok(self.saved_state.take())
+    }
+
+    fn on_syscall(&mut self, nr: u32, ctx: &mut dyn SyscallContext) -> Result<Option<(u32, u32)>> {
+        if let Some(ref mut replay) = self.opts.replay {
+            return Ok(Some(replay.syscall(nr, ctx)?));
+        }
+        let handler = match self.opts.syscall_handlers.get_mut(&nr) {
+            Some(handler) => handler,
+            None => return Ok(None),
+        };
+        match self.opts.trace_writer {
+            Some(ref mut writer) => {
+                let mut ctx = trace::RecordingContext {
+                    inner: ctx,
+                    stores: Vec::new(),
+                };
+                let ret = handler.syscall(&mut ctx)?;
+                writer.syscall(nr, ret, &ctx.stores)?;
+                Ok(Some(ret))
+            }
+            None => Ok(Some(handler.syscall(ctx)?)),
+        }
+    }
+
     fn on_fault(&mut self, msg: &str) -> Result<()> {
-        bail!("{}", msg)
+        log::debug!("Guest fault: {msg}");
+        // Make sure the trace leading up to the fault is on disk.
+        self.flush_trace()
+    }
+
+    #[cfg(feature = "gdb")]
+    fn gdb_stub(&mut self) -> Option<&mut gdb::GdbStub> {
+        let writable = self.opts.skip_seal || insecure_skip_seal();
+        self.opts.gdb_stub.as_mut().map(|stub| {
+            stub.set_writable(writable);
+            stub
+        })
     }
 }
 
@@ -316,7 +1154,9 @@ fn merge_word8((x0, x1, x2, x3): (BabyBearElem, BabyBearElem, BabyBearElem, Baby
     x0 | x1 << 8 | x2 << 16 | x3 << 24
 }
 
-/// An event traced from the running VM.
+/// An event traced from the running VM while it's being proven by
+/// [Prover::run].
+#[derive(Clone)]
 #[non_exhaustive]
 pub enum TraceEvent {
     /// An instruction has started at the given program counter
@@ -342,6 +1182,53 @@ pub enum TraceEvent {
         /// Value of word that's been written
         value: u32,
     },
+
+    /// A memory location has been read
+    MemoryRead {
+        /// Address of word that's been read
+        addr: u32,
+        /// Value of word that's been read
+        value: u32,
+    },
+
+    /// A page of guest memory has been accessed for the first time, either
+    /// by the loader or by the guest
+    PageTouched {
+        /// Address of the start of the [PAGE_SIZE] byte page
+        addr: u32,
+    },
+
+    /// The guest has invoked an ecall
+    Ecall {
+        /// Kind of ecall, from `risc0_zkvm_platform::syscall::ecall`
+        kind: u32,
+    },
+
+    /// The guest has invoked a syscall
+    SyscallStart {
+        /// Syscall number
+        nr: u32,
+        /// Values of registers a0-a4
+        args: [u32; 5],
+    },
+
+    /// A syscall has returned to the guest
+    SyscallEnd {
+        /// Values returned in registers a0 and a1
+        ret: [u32; 2],
+    },
+
+    /// The guest has committed data to the journal
+    Commit {
+        /// Number of bytes committed
+        len: u32,
+    },
+
+    /// The guest has halted
+    Halt {
+        /// Cycle number since startup
+        cycle: u32,
+    },
 }
 
 impl Debug for TraceEvent {
@@ -352,6 +1239,15 @@ impl Debug for TraceEvent {
             }
             Self::RegisterSet { reg, value } => write!(f, "RegisterSet({reg}, 0x{value:08X})"),
             Self::MemorySet { addr, value } => write!(f, "MemorySet(0x{addr:08X}, 0x{value:08X})"),
+            Self::MemoryRead { addr, value } => {
+                write!(f, "MemoryRead(0x{addr:08X}, 0x{value:08X})")
+            }
+            Self::PageTouched { addr } => write!(f, "PageTouched(0x{addr:08X})"),
+            Self::Ecall { kind } => write!(f, "Ecall({kind})"),
+            Self::SyscallStart { nr, args } => write!(f, "SyscallStart({nr}, {args:08X?})"),
+            Self::SyscallEnd { ret } => write!(f, "SyscallEnd({ret:08X?})"),
+            Self::Commit { len } => write!(f, "Commit({len})"),
+            Self::Halt { cycle } => write!(f, "Halt({cycle})"),
         }
     }
 }
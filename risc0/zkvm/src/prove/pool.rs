@@ -0,0 +1,184 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A pool of worker threads for proving many receipts concurrently.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use super::{Prover, ProverOpts};
+use crate::{method_id::MethodId, receipt::Receipt};
+
+type OptsFactory = dyn Fn() -> ProverOpts<'static> + Send + Sync;
+
+struct Job {
+    elf: Arc<[u8]>,
+    method_id: MethodId,
+    input: Vec<u8>,
+    reply: SyncSender<Result<Receipt>>,
+}
+
+/// A handle to the result of a job submitted to a [ProverPool].
+pub struct ProveHandle {
+    reply: Receiver<Result<Receipt>>,
+}
+
+impl ProveHandle {
+    /// Blocks until the job is done, and returns its receipt.
+    pub fn wait(self) -> Result<Receipt> {
+        self.reply
+            .recv()
+            .map_err(|_| anyhow!("Prover worker exited without a result"))?
+    }
+
+    /// Returns the receipt if the job is done, or `None` if it is still
+    /// queued or running.
+    pub fn try_wait(&self) -> Option<Result<Receipt>> {
+        match self.reply.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Some(Err(anyhow!("Prover worker exited without a result")))
+            }
+        }
+    }
+}
+
+/// A pool of threads which generate receipts concurrently.
+///
+/// Each worker thread has its own HAL and `EvalCheck`.  Jobs wait in a
+/// bounded queue until a worker is free; [ProverPool::submit] blocks while
+/// the queue is full.  Dropping the pool waits for the queued jobs to finish.
+pub struct ProverPool {
+    jobs: Option<SyncSender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ProverPool {
+    /// Starts a pool of `workers` threads, with room for `queue_depth` jobs
+    /// waiting for a free worker.
+    pub fn new(workers: usize, queue_depth: usize) -> Self {
+        Self::with_opts(workers, queue_depth, ProverOpts::default)
+    }
+
+    /// Like [ProverPool::new], but calls `opts` to build the options for
+    /// each job.
+    pub fn with_opts(
+        workers: usize,
+        queue_depth: usize,
+        opts: impl Fn() -> ProverOpts<'static> + Send + Sync + 'static,
+    ) -> Self {
+        assert!(workers > 0, "A prover pool needs at least one worker");
+        let (sender, receiver) = mpsc::sync_channel::<Job>(queue_depth);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let opts: Arc<OptsFactory> = Arc::new(opts);
+        let workers = (0..workers)
+            .map(|idx| {
+                let receiver = receiver.clone();
+                let opts = opts.clone();
+                thread::Builder::new()
+                    .name(format!("prover-{idx}"))
+                    .spawn(move || worker(&receiver, opts.as_ref()))
+                    .expect("Failed to spawn prover worker")
+            })
+            .collect();
+        Self {
+            jobs: Some(sender),
+            workers,
+        }
+    }
+
+    /// Queues a job to prove `elf` with the given initial input, blocking
+    /// while the queue is full.
+    pub fn submit(
+        &self,
+        elf: Arc<[u8]>,
+        method_id: MethodId,
+        input: Vec<u8>,
+    ) -> Result<ProveHandle> {
+        let (job, handle) = new_job(elf, method_id, input);
+        self.sender()
+            .send(job)
+            .map_err(|_| anyhow!("Prover pool has shut down"))?;
+        Ok(handle)
+    }
+
+    /// Like [ProverPool::submit], but fails instead of blocking if the queue
+    /// is full.
+    pub fn try_submit(
+        &self,
+        elf: Arc<[u8]>,
+        method_id: MethodId,
+        input: Vec<u8>,
+    ) -> Result<ProveHandle> {
+        let (job, handle) = new_job(elf, method_id, input);
+        match self.sender().try_send(job) {
+            Ok(()) => Ok(handle),
+            Err(TrySendError::Full(_)) => bail!("Prover pool queue is full"),
+            Err(TrySendError::Disconnected(_)) => bail!("Prover pool has shut down"),
+        }
+    }
+
+    fn sender(&self) -> &SyncSender<Job> {
+        self.jobs.as_ref().expect("Prover pool is running")
+    }
+}
+
+impl Drop for ProverPool {
+    fn drop(&mut self) {
+        // Closing the queue stops the workers once it's empty.
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn new_job(elf: Arc<[u8]>, method_id: MethodId, input: Vec<u8>) -> (Job, ProveHandle) {
+    let (reply, receiver) = mpsc::sync_channel(1);
+    let job = Job {
+        elf,
+        method_id,
+        input,
+        reply,
+    };
+    (job, ProveHandle { reply: receiver })
+}
+
+fn worker(jobs: &Mutex<Receiver<Job>>, opts: &OptsFactory) {
+    loop {
+        // Only hold the lock while waiting for a job, so other workers can
+        // pick up jobs while this one is proving.
+        let job = match jobs.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut prover = Prover::new_with_opts(&job.elf, job.method_id.clone(), opts())?;
+            prover.add_input_u8_slice(&job.input);
+            prover.run()
+        }))
+        .unwrap_or_else(|_| Err(anyhow!("Prover panicked")));
+        // The caller may have dropped its handle.
+        let _ = job.reply.send(result);
+    }
+}
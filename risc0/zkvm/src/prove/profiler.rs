@@ -27,13 +27,18 @@
 //
 //  * Demangle symbols
 
-use std::collections::HashMap;
+mod flamegraph;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
 
 use addr2line::{
     object::{read::File, Object, ObjectSegment},
     Context,
 };
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use gimli::{EndianRcSlice, RunTimeEndian};
 use prost::Message;
 
@@ -189,6 +194,42 @@ impl Profiler {
         &self.profile.profile
     }
 
+    // Returns the cycles spent in each stack, keyed by the frame names
+    // separated by semicolons, outermost frame first.
+    fn folded_stacks(&self) -> BTreeMap<String, u64> {
+        assert!(
+            !self.profile.profile.sample.is_empty(),
+            "Call finalize() first to generate the stacks"
+        );
+        let mut stacks = BTreeMap::new();
+        for (frames, pc, count) in self.iter() {
+            let stack = if frames.is_empty() {
+                format!("0x{pc:08x}")
+            } else {
+                let names: Vec<&str> = frames.iter().rev().map(|fr| fr.name.as_str()).collect();
+                names.join(";")
+            };
+            *stacks.entry(stack).or_insert(0) += count as u64;
+        }
+        stacks
+    }
+
+    /// Writes the result of this profiling run as folded stacks, the format
+    /// used by Brendan Gregg's flamegraph tools.  Each line holds a stack,
+    /// with frames separated by semicolons, followed by its cycle count.
+    pub fn write_folded(&self, out: &mut impl Write) -> Result<()> {
+        for (stack, count) in self.folded_stacks() {
+            writeln!(out, "{stack} {count}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the result of this profiling run as a self-contained SVG
+    /// flamegraph.
+    pub fn write_flamegraph(&self, title: &str, out: &mut impl Write) -> Result<()> {
+        flamegraph::write_svg(&self.folded_stacks(), title, out)
+    }
+
     /// Returns the result of this profiling run, encoded and ready for writing
     /// to a file.
     pub fn encode_to_vec(&mut self) -> Vec<u8> {
@@ -196,6 +237,85 @@ impl Profiler {
     }
 }
 
+/// The cycles spent in a function in two profiles, as found by
+/// [diff_folded].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionDelta {
+    /// Function name
+    pub name: String,
+    /// Cycles spent in the function in the first profile
+    pub before: u64,
+    /// Cycles spent in the function in the second profile
+    pub after: u64,
+}
+
+impl FunctionDelta {
+    /// Returns the change in cycles from the first profile to the second.
+    pub fn delta(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+}
+
+// Returns the cycles spent in each function, not counting the functions it
+// calls, from folded stacks.
+fn parse_folded(folded: &str) -> Result<BTreeMap<String, u64>> {
+    let mut functions = BTreeMap::new();
+    for line in folded.lines().filter(|line| !line.trim().is_empty()) {
+        let (stack, count) = line
+            .rsplit_once(' ')
+            .ok_or_else(|| anyhow!("Malformed folded stack: {line}"))?;
+        let count: u64 = count
+            .parse()
+            .map_err(|_| anyhow!("Malformed folded stack: {line}"))?;
+        let leaf = stack.rsplit(';').next().unwrap_or(stack);
+        *functions.entry(leaf.to_string()).or_insert(0) += count;
+    }
+    Ok(functions)
+}
+
+/// Compares two profiles in the folded stack format written by
+/// [Profiler::write_folded].  Returns the change in cycles spent in each
+/// function, not counting the functions it calls, largest changes first.
+/// Functions whose cycle counts are unchanged are left out.
+pub fn diff_folded(before: &str, after: &str) -> Result<Vec<FunctionDelta>> {
+    let before = parse_folded(before)?;
+    let after = parse_folded(after)?;
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort();
+    names.dedup();
+    let mut deltas: Vec<FunctionDelta> = names
+        .into_iter()
+        .map(|name| FunctionDelta {
+            name: name.clone(),
+            before: before.get(name).copied().unwrap_or(0),
+            after: after.get(name).copied().unwrap_or(0),
+        })
+        .filter(|delta| delta.delta() != 0)
+        .collect();
+    deltas.sort_by_key(|delta| std::cmp::Reverse(delta.delta().unsigned_abs()));
+    Ok(deltas)
+}
+
+/// Writes a table of the function deltas found by [diff_folded].
+pub fn write_diff(deltas: &[FunctionDelta], out: &mut impl Write) -> Result<()> {
+    writeln!(
+        out,
+        "{:>12} {:>12} {:>12}  function",
+        "before", "after", "delta"
+    )?;
+    for delta in deltas {
+        writeln!(
+            out,
+            "{:>12} {:>12} {:>+12}  {}",
+            delta.before,
+            delta.after,
+            delta.delta(),
+            delta.name
+        )?;
+    }
+    Ok(())
+}
+
 struct ProfileBuilder {
     strings: HashMap<String, i64>,
 
@@ -0,0 +1,177 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A self-contained SVG flamegraph writer for folded stacks.
+
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+
+const WIDTH: f64 = 1200.0;
+const FRAME_HEIGHT: f64 = 16.0;
+const FONT_SIZE: f64 = 12.0;
+// Approximate width of a character, relative to the font size.
+const FONT_WIDTH: f64 = 0.59;
+const PAD_TOP: f64 = FONT_SIZE * 3.0;
+const PAD_BOTTOM: f64 = FONT_SIZE;
+const PAD_SIDE: f64 = 10.0;
+// Frames narrower than this many pixels aren't drawn.
+const MIN_FRAME_WIDTH: f64 = 0.1;
+
+#[derive(Default)]
+struct Node {
+    total: u64,
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    fn depth(&self) -> usize {
+        self.children
+            .values()
+            .map(|child| child.depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+struct Layout<'a, W: Write> {
+    out: &'a mut W,
+    height: f64,
+    // Pixels per cycle
+    scale: f64,
+    total: u64,
+}
+
+impl<'a, W: Write> Layout<'a, W> {
+    fn write_frame(&mut self, name: &str, node: &Node, depth: usize, x: f64) -> Result<()> {
+        let width = node.total as f64 * self.scale;
+        if width < MIN_FRAME_WIDTH {
+            return Ok(());
+        }
+        let y = self.height - PAD_BOTTOM - (depth + 1) as f64 * FRAME_HEIGHT;
+        let percent = node.total as f64 * 100.0 / self.total as f64;
+        let escaped = escape(name);
+        writeln!(
+            self.out,
+            r#"<g><title>{escaped} ({} cycles, {percent:.2}%)</title>"#,
+            node.total
+        )?;
+        writeln!(
+            self.out,
+            r#"<rect x="{x:.1}" y="{y:.1}" width="{width:.1}" height="{:.1}" fill="{}" rx="2" ry="2"/>"#,
+            FRAME_HEIGHT - 1.0,
+            color(name)
+        )?;
+        let max_chars = (width / (FONT_SIZE * FONT_WIDTH)) as usize;
+        if max_chars >= 3 {
+            let label = if name.chars().count() > max_chars {
+                let truncated: String = name.chars().take(max_chars - 2).collect();
+                format!("{}..", escape(&truncated))
+            } else {
+                escaped
+            };
+            writeln!(
+                self.out,
+                r#"<text x="{:.1}" y="{:.1}">{label}</text>"#,
+                x + 3.0,
+                y + FRAME_HEIGHT - 4.0
+            )?;
+        }
+        writeln!(self.out, "</g>")?;
+
+        let mut child_x = x;
+        for (child_name, child) in node.children.iter() {
+            self.write_frame(child_name, child, depth + 1, child_x)?;
+            child_x += child.total as f64 * self.scale;
+        }
+        Ok(())
+    }
+}
+
+/// Writes an SVG flamegraph of the given folded stacks, which map
+/// semicolon-separated stacks (outermost frame first) to cycle counts.
+pub fn write_svg(stacks: &BTreeMap<String, u64>, title: &str, out: &mut impl Write) -> Result<()> {
+    let mut root = Node::default();
+    for (stack, count) in stacks.iter() {
+        root.total += count;
+        let mut node = &mut root;
+        for frame in stack.split(';') {
+            node = node.children.entry(frame.to_string()).or_default();
+            node.total += count;
+        }
+    }
+
+    let height = PAD_TOP + (root.depth() + 1) as f64 * FRAME_HEIGHT + PAD_BOTTOM;
+    writeln!(out, r#"<?xml version="1.0" standalone="no"?>"#)?;
+    writeln!(
+        out,
+        r#"<svg version="1.1" width="{WIDTH}" height="{height}" viewBox="0 0 {WIDTH} {height}" xmlns="http://www.w3.org/2000/svg">"#
+    )?;
+    writeln!(
+        out,
+        r#"<style>text {{ font-family: monospace; font-size: {FONT_SIZE}px; fill: black; }} rect:hover {{ stroke: black; }}</style>"#
+    )?;
+    writeln!(
+        out,
+        r#"<rect x="0" y="0" width="100%" height="100%" fill="white"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"<text x="{:.1}" y="{:.1}" text-anchor="middle" style="font-size: {}px">{}</text>"#,
+        WIDTH / 2.0,
+        FONT_SIZE * 2.0,
+        FONT_SIZE + 5.0,
+        escape(title)
+    )?;
+    if root.total > 0 {
+        let mut layout = Layout {
+            out,
+            height,
+            scale: (WIDTH - 2.0 * PAD_SIDE) / root.total as f64,
+            total: root.total,
+        };
+        layout.write_frame("all", &root, 0, PAD_SIDE)?;
+    }
+    writeln!(out, "</svg>")?;
+    Ok(())
+}
+
+// Escapes text for inclusion in XML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+// Picks a warm color for a frame, based on its name so that the same function
+// always has the same color.
+fn color(name: &str) -> String {
+    // FNV-1a
+    let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    let r = 205 + hash % 50;
+    let g = (hash >> 8) % 230;
+    let b = (hash >> 16) % 55;
+    format!("rgb({r},{g},{b})")
+}
@@ -0,0 +1,162 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{cell::Cell, collections::BTreeMap};
+
+use risc0_zkvm_platform::{
+    memory::{Region, COMMIT, DATA, GPIO, HEAP, INPUT, MEM_SIZE, OUTPUT, PROG, SHA, STACK},
+    syscall::reg_abi::REG_SP,
+    WORD_SIZE,
+};
+use serde::{Deserialize, Serialize};
+
+/// Size of the pages used to count memory usage, in bytes.
+pub const PAGE_SIZE: u32 = 1024;
+
+// The regions reported in MemoryStats.  WOM overlaps OUTPUT, and the SYSTEM
+// region lies outside of the guest's memory.
+const REGIONS: &[(&str, &Region)] = &[
+    ("STACK", &STACK),
+    ("DATA", &DATA),
+    ("HEAP", &HEAP),
+    ("INPUT", &INPUT),
+    ("GPIO", &GPIO),
+    ("PROG", &PROG),
+    ("SHA", &SHA),
+    ("OUTPUT", &OUTPUT),
+    ("COMMIT", &COMMIT),
+];
+
+/// Statistics on the guest's use of memory during a run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The peak number of words of the heap in use.  The guest's allocators
+    /// take memory from the start of `HEAP` upwards and never return it, so
+    /// this counts the words from the start of `HEAP` up to the highest word
+    /// written.
+    pub heap_peak_words: usize,
+    /// The lowest value of the stack pointer, or `None` if the guest never
+    /// set it.
+    pub stack_low_water: Option<u32>,
+    /// The number of distinct [PAGE_SIZE] byte pages touched in each region
+    /// of `risc0_zkvm_platform::memory`, indexed by region name.
+    pub pages_touched: BTreeMap<&'static str, usize>,
+}
+
+impl MemoryStats {
+    /// The number of bytes of `STACK` in use when the stack was deepest.
+    pub fn stack_peak_bytes(&self) -> usize {
+        self.stack_low_water
+            .map_or(0, |sp| STACK.end().saturating_sub(sp as usize))
+    }
+}
+
+/// The state of a [MemoryTracker], saved in a snapshot so that a resumed run
+/// reports the memory used before it was paused, too.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct TrackerState {
+    // The numbers of the pages touched.
+    pages: Vec<u32>,
+    heap_end: usize,
+    stack_low_water: Option<u32>,
+}
+
+/// Tracks the guest's use of memory as it runs.
+pub(crate) struct MemoryTracker {
+    // Whether each page of guest memory has been touched.  Loads take &self,
+    // so this uses interior mutability.
+    pages: Vec<Cell<bool>>,
+    // End of the highest word written in the heap, relative to its start.
+    heap_end: usize,
+    stack_low_water: Option<u32>,
+}
+
+impl MemoryTracker {
+    pub fn new() -> Self {
+        Self {
+            pages: vec![Cell::new(false); MEM_SIZE / PAGE_SIZE as usize],
+            heap_end: 0,
+            stack_low_water: None,
+        }
+    }
+
+    /// Restores a tracker saved with [MemoryTracker::save].
+    pub fn restore(state: &TrackerState) -> Self {
+        let tracker = Self {
+            heap_end: state.heap_end,
+            stack_low_water: state.stack_low_water,
+            ..Self::new()
+        };
+        for page in state.pages.iter() {
+            tracker.touch(page * PAGE_SIZE);
+        }
+        tracker
+    }
+
+    pub fn save(&self) -> TrackerState {
+        TrackerState {
+            pages: (0..self.pages.len() as u32)
+                .filter(|page| self.pages[*page as usize].get())
+                .collect(),
+            heap_end: self.heap_end,
+            stack_low_water: self.stack_low_water,
+        }
+    }
+
+    /// Records an access to the given address.  Returns true if this is the
+    /// first access to its page.
+    pub fn touch(&self, addr: u32) -> bool {
+        match self.pages.get((addr / PAGE_SIZE) as usize) {
+            Some(page) => !page.replace(true),
+            None => false,
+        }
+    }
+
+    /// Records a write to the word at the given address.  Returns true if
+    /// this is the first access to its page.
+    pub fn write(&mut self, addr: u32) -> bool {
+        let addr = addr as usize;
+        if addr >= HEAP.start() && addr < HEAP.end() {
+            self.heap_end = self.heap_end.max(addr + WORD_SIZE - HEAP.start());
+        }
+        self.touch(addr as u32)
+    }
+
+    /// Records a write to a register.
+    pub fn set_register(&mut self, reg: usize, value: u32) {
+        // The loader clears the registers before the guest sets up its stack.
+        if reg == REG_SP && value != 0 {
+            self.stack_low_water = Some(self.stack_low_water.map_or(value, |sp| sp.min(value)));
+        }
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let pages_per_region = |region: &Region| {
+            let first = region.start() / PAGE_SIZE as usize;
+            let last = region.end() / PAGE_SIZE as usize;
+            self.pages[first..last.min(self.pages.len())]
+                .iter()
+                .filter(|page| page.get())
+                .count()
+        };
+        MemoryStats {
+            heap_peak_words: self.heap_end / WORD_SIZE,
+            stack_low_water: self.stack_low_water,
+            pages_touched: REGIONS
+                .iter()
+                .map(|(name, region)| (*name, pages_per_region(region)))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for host-side handlers of custom guest syscalls.
+//!
+//! Guests invoke custom syscalls with
+//! [risc0_zkvm_platform::syscall::sys_user], using syscall numbers at or above
+//! [risc0_zkvm_platform::syscall::nr::SYS_USER_BASE].  The host services them
+//! with a [SyscallHandler] registered with
+//! [crate::ProverOpts::with_syscall].
+
+use anyhow::{anyhow, Result};
+
+/// A view of the guest's registers and memory, available to a
+/// [SyscallHandler] while it handles a syscall.
+pub trait SyscallContext {
+    /// Returns the current value of the given guest register.  See
+    /// [risc0_zkvm_platform::syscall::reg_abi] for register numbers; any
+    /// other number is an error.
+    fn load_register(&self, reg: usize) -> Result<u32>;
+
+    /// Reads `size` bytes of guest memory, starting at `addr`.
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>>;
+
+    /// Writes the given bytes to guest memory, starting at `addr`.
+    fn store_region(&mut self, addr: u32, data: &[u8]) -> Result<()>;
+
+    /// Returns the current cycle count.
+    fn cycle(&self) -> usize;
+}
+
+// Returns the value of the given register, as seen by a SyscallContext.
+pub(crate) fn get_register(regs: &[u32; 32], reg: usize) -> Result<u32> {
+    regs.get(reg)
+        .copied()
+        .ok_or_else(|| anyhow!("Invalid register number {reg}"))
+}
+
+/// A host-side handler for a custom guest syscall.
+pub trait SyscallHandler {
+    /// Handles a syscall from the guest, returning the values to place in
+    /// registers `a0` and `a1`.  Any error aborts execution.
+    fn syscall(&mut self, ctx: &mut dyn SyscallContext) -> Result<(u32, u32)>;
+}
+
+impl<F> SyscallHandler for F
+where
+    F: FnMut(&mut dyn SyscallContext) -> Result<(u32, u32)>,
+{
+    fn syscall(&mut self, ctx: &mut dyn SyscallContext) -> Result<(u32, u32)> {
+        self(ctx)
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recording of execution traces to a file, and offline replay of them.
+//!
+//! A trace recorded with [crate::ProverOpts::with_trace_file] holds the
+//! instructions executed, the register and memory writes they made, and the
+//! replies the host gave to the guest's syscalls.  [Replay] reads a trace
+//! back and serves the recorded replies to the guest instead of the original
+//! host callbacks, checking that the guest executes exactly as it did when
+//! the trace was recorded.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+
+use super::{SyscallContext, TraceEvent};
+use crate::{MethodId, Prover, ProverOpts, Receipt};
+
+const MAGIC: &[u8; 4] = b"R0TR";
+const VERSION: u32 = 1;
+
+const TAG_INSTRUCTION_START: u8 = 1;
+const TAG_REGISTER_SET: u8 = 2;
+const TAG_MEMORY_SET: u8 = 3;
+const TAG_TXRX: u8 = 4;
+const TAG_READ: u8 = 5;
+const TAG_SYSCALL: u8 = 6;
+const TAG_RANDOM: u8 = 7;
+const TAG_ENV: u8 = 8;
+
+// Returns the tag and fields of the trace events which are recorded.
+fn encode_event(event: &TraceEvent) -> Option<(u8, u32, u32)> {
+    match *event {
+        TraceEvent::InstructionStart { cycle, pc } => Some((TAG_INSTRUCTION_START, cycle, pc)),
+        TraceEvent::RegisterSet { reg, value } => Some((TAG_REGISTER_SET, reg as u32, value)),
+        TraceEvent::MemorySet { addr, value } => Some((TAG_MEMORY_SET, addr, value)),
+        _ => None,
+    }
+}
+
+/// Writes a trace to a file as the guest runs.
+pub(crate) struct TraceWriter {
+    out: BufWriter<File>,
+}
+
+impl TraceWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create trace file {}", path.display()))?;
+        let mut writer = Self {
+            out: BufWriter::new(file),
+        };
+        writer.out.write_all(MAGIC)?;
+        writer.write_u32(VERSION)?;
+        Ok(writer)
+    }
+
+    pub fn event(&mut self, event: &TraceEvent) -> Result<()> {
+        if let Some((tag, a, b)) = encode_event(event) {
+            self.out.write_all(&[tag])?;
+            self.write_u32(a)?;
+            self.write_u32(b)?;
+        }
+        Ok(())
+    }
+
+    pub fn txrx(&mut self, channel: u32, reply: &[u8]) -> Result<()> {
+        self.out.write_all(&[TAG_TXRX])?;
+        self.write_u32(channel)?;
+        self.write_bytes(reply)
+    }
+
+    pub fn read(&mut self, fd: u32, data: &[u8]) -> Result<()> {
+        self.out.write_all(&[TAG_READ])?;
+        self.write_u32(fd)?;
+        self.write_bytes(data)
+    }
+
+    pub fn random(&mut self, data: &[u8]) -> Result<()> {
+        self.out.write_all(&[TAG_RANDOM])?;
+        self.write_bytes(data)
+    }
+
+    pub fn env(&mut self, value: Option<&[u8]>) -> Result<()> {
+        self.out.write_all(&[TAG_ENV])?;
+        match value {
+            Some(value) => {
+                self.write_u32(1)?;
+                self.write_bytes(value)
+            }
+            None => self.write_u32(0),
+        }
+    }
+
+    pub fn syscall(&mut self, nr: u32, ret: (u32, u32), stores: &[(u32, Vec<u8>)]) -> Result<()> {
+        self.out.write_all(&[TAG_SYSCALL])?;
+        self.write_u32(nr)?;
+        self.write_u32(ret.0)?;
+        self.write_u32(ret.1)?;
+        self.write_u32(stores.len() as u32)?;
+        for (addr, data) in stores {
+            self.write_u32(*addr)?;
+            self.write_bytes(data)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush().context("Failed to write trace file")
+    }
+
+    fn write_u32(&mut self, value: u32) -> Result<()> {
+        Ok(self.out.write_all(&value.to_le_bytes())?)
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) -> Result<()> {
+        self.write_u32(data.len() as u32)?;
+        Ok(self.out.write_all(data)?)
+    }
+}
+
+/// Gives a syscall handler access to the guest, keeping track of the memory
+/// it writes so it can be recorded.
+pub(crate) struct RecordingContext<'a> {
+    pub inner: &'a mut dyn SyscallContext,
+    pub stores: Vec<(u32, Vec<u8>)>,
+}
+
+impl<'a> SyscallContext for RecordingContext<'a> {
+    fn load_register(&self, reg: usize) -> Result<u32> {
+        self.inner.load_register(reg)
+    }
+
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>> {
+        self.inner.load_region(addr, size)
+    }
+
+    fn store_region(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        self.inner.store_region(addr, data)?;
+        self.stores.push((addr, data.to_vec()));
+        Ok(())
+    }
+
+    fn cycle(&self) -> usize {
+        self.inner.cycle()
+    }
+}
+
+/// Replays a trace recorded with [crate::ProverOpts::with_trace_file].
+///
+/// A trace must be replayed the same way it was recorded: a trace recorded
+/// by [Prover::run] can only be replayed by [Prover::run], and one recorded
+/// by [Prover::execute] only by [Prover::execute].
+pub struct Replay {
+    input: Box<dyn Read>,
+}
+
+impl Replay {
+    /// Opens the trace in the given file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open trace file {}", path.display()))?;
+        Self::new(BufReader::new(file))
+    }
+
+    /// Reads a trace from the given reader.
+    pub fn new(input: impl Read + 'static) -> Result<Self> {
+        let mut replay = Self {
+            input: Box::new(input),
+        };
+        let mut magic = [0; 4];
+        replay.input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            bail!("Not a trace file");
+        }
+        let version = replay.read_u32()?;
+        if version != VERSION {
+            bail!("Unsupported trace version {version}, expected {VERSION}");
+        }
+        Ok(replay)
+    }
+
+    /// Replays the trace against the given ELF with [Prover::run], without
+    /// generating a seal.
+    pub fn run(self, elf: &[u8]) -> Result<Receipt> {
+        let opts = ProverOpts::default().with_skip_seal(true);
+        self.run_with_opts(elf, MethodId { table: Vec::new() }, opts)
+    }
+
+    /// Replays the trace against the given ELF with [Prover::run], using the
+    /// given options, e.g. to generate a seal for the given method ID.
+    pub fn run_with_opts<'a, M>(
+        self,
+        elf: &[u8],
+        method_id: M,
+        opts: ProverOpts<'a>,
+    ) -> Result<Receipt>
+    where
+        MethodId: From<M>,
+    {
+        let mut prover = Prover::new_with_opts(elf, method_id, opts.with_replay(self))?;
+        prover.run()
+    }
+
+    /// Checks that the guest produced the recorded trace event.
+    pub(crate) fn check_event(&mut self, event: &TraceEvent) -> Result<()> {
+        let (tag, a, b) = match encode_event(event) {
+            Some(encoded) => encoded,
+            None => return Ok(()),
+        };
+        self.expect_tag(tag)?;
+        let expected = (self.read_u32()?, self.read_u32()?);
+        if expected != (a, b) {
+            bail!("Replay diverged: expected {expected:?}, but the guest produced {event:?}");
+        }
+        Ok(())
+    }
+
+    /// Returns the recorded reply for a send on the given channel.
+    pub(crate) fn txrx(&mut self, channel: u32) -> Result<Vec<u8>> {
+        self.expect_tag(TAG_TXRX)?;
+        let expected = self.read_u32()?;
+        if expected != channel {
+            bail!("Replay diverged: expected a send on channel {expected}, got channel {channel}");
+        }
+        self.read_bytes()
+    }
+
+    /// Returns the recorded data for a read from the given file descriptor.
+    pub(crate) fn read(&mut self, fd: u32) -> Result<Vec<u8>> {
+        self.expect_tag(TAG_READ)?;
+        let expected = self.read_u32()?;
+        if expected != fd {
+            bail!("Replay diverged: expected a read from fd {expected}, got fd {fd}");
+        }
+        self.read_bytes()
+    }
+
+    /// Returns the recorded random bytes given to the guest.
+    pub(crate) fn random(&mut self, nbytes: usize) -> Result<Vec<u8>> {
+        self.expect_tag(TAG_RANDOM)?;
+        let data = self.read_bytes()?;
+        if data.len() != nbytes {
+            bail!(
+                "Replay diverged: expected a request for {} random bytes, got {nbytes}",
+                data.len()
+            );
+        }
+        Ok(data)
+    }
+
+    /// Returns the recorded command-line argument or environment variable
+    /// given to the guest.
+    pub(crate) fn env(&mut self) -> Result<Option<Vec<u8>>> {
+        self.expect_tag(TAG_ENV)?;
+        match self.read_u32()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.read_bytes()?)),
+        }
+    }
+
+    /// Applies the recorded memory writes of a custom syscall, and returns
+    /// its recorded result.
+    pub(crate) fn syscall(&mut self, nr: u32, ctx: &mut dyn SyscallContext) -> Result<(u32, u32)> {
+        self.expect_tag(TAG_SYSCALL)?;
+        let expected = self.read_u32()?;
+        if expected != nr {
+            bail!("Replay diverged: expected syscall {expected}, got syscall {nr}");
+        }
+        let ret = (self.read_u32()?, self.read_u32()?);
+        for _ in 0..self.read_u32()? {
+            let addr = self.read_u32()?;
+            let data = self.read_bytes()?;
+            ctx.store_region(addr, &data)?;
+        }
+        Ok(ret)
+    }
+
+    /// Checks that the guest produced every record in the trace.
+    pub(crate) fn finish(&mut self) -> Result<()> {
+        let mut found = [0];
+        if self.input.read(&mut found)? != 0 {
+            bail!("Replay diverged: trace has unconsumed records");
+        }
+        Ok(())
+    }
+
+    fn expect_tag(&mut self, tag: u8) -> Result<()> {
+        let mut found = [0];
+        match self.input.read_exact(&mut found) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                bail!("Replay diverged: the guest ran past the end of the trace")
+            }
+            Err(err) => return Err(err.into()),
+        }
+        if found[0] != tag {
+            bail!(
+                "Replay diverged: the trace has a record of type {}, but the guest produced one of type {tag}",
+                found[0]
+            );
+        }
+        Ok(())
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        self.input
+            .read_exact(&mut bytes)
+            .context("Truncated trace")?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_u32()?;
+        let mut data = Vec::new();
+        self.input
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut data)?;
+        if data.len() != len as usize {
+            bail!("Truncated trace");
+        }
+        Ok(data)
+    }
+}
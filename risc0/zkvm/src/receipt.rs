@@ -14,9 +14,9 @@
 
 use alloc::vec::Vec;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use risc0_zeroio::{Deserialize as ZeroioDeserialize, Serialize as ZeroioSerialize};
-use risc0_zkp::{core::sha::Digest, MIN_CYCLES_PO2};
+use risc0_zkp::{core::sha::Digest, MAX_CYCLES_PO2, MIN_CYCLES_PO2};
 use serde::{Deserialize, Serialize};
 
 use crate::{method_id::MethodId, CIRCUIT};
@@ -25,9 +25,153 @@ use crate::{method_id::MethodId, CIRCUIT};
 pub struct Receipt {
     pub journal: Vec<u32>,
     pub seal: Vec<u32>,
+    /// How the guest stopped.  This is committed to the seal along with the
+    /// journal, so a receipt proves the exit code as well as the journal.
+    pub exit_code: ExitCode,
 }
 
-pub fn verify_with_hal<H, M>(hal: &H, method_id: M, seal: &[u32], journal: &[u32]) -> Result<()>
+/// How a guest stopped executing.
+///
+/// The guest passes its exit code to the host in registers a0 and a1 when it
+/// halts, and writes the same two words to the seal's outputs after the
+/// journal.  The seal's output length has its lowest bit set to tell such
+/// seals apart from those of older guests, which only commit to the journal.
+#[derive(
+    Deserialize, Serialize, ZeroioSerialize, ZeroioDeserialize, Clone, Copy, Debug, PartialEq, Eq,
+)]
+pub enum ExitCode {
+    /// The guest halted with the given exit code.  Returning from the guest's
+    /// main function halts with an exit code of 0; a guest may halt with
+    /// another exit code with `risc0_zkvm::guest::env::exit`, e.g. to prove
+    /// that its input was rejected.
+    Halted(u32),
+    /// The guest paused, and may be resumed later.
+    Paused,
+    /// The guest panicked.  The journal holds what the guest committed
+    /// before it panicked.
+    Faulted,
+}
+
+/// Set in the seal's output length when the seal commits to an exit code.
+/// Output lengths are otherwise a whole number of words, so this bit is
+/// always clear for guests which only commit to their journal.
+pub(crate) const EXIT_CODE_FLAG: usize = 1;
+
+/// Set in the seal's output length when the guest resumed from a paused
+/// state, whose digest the seal commits to after the journal.
+pub(crate) const RESUMED_FLAG: usize = 2;
+
+impl ExitCode {
+    /// The number of words an exit code takes up after the journal.
+    pub(crate) const WORDS: usize = 2;
+
+    // Exit codes are written as a kind followed by the code passed to halt,
+    // if any.
+    pub(crate) fn to_words(&self) -> [u32; Self::WORDS] {
+        match *self {
+            ExitCode::Halted(code) => [0, code],
+            ExitCode::Paused => [1, 0],
+            ExitCode::Faulted => [2, 0],
+        }
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    pub(crate) fn from_words(words: [u32; Self::WORDS]) -> Result<Self> {
+        match words {
+            [0, code] => Ok(ExitCode::Halted(code)),
+            [1, 0] => Ok(ExitCode::Paused),
+            [2, 0] => Ok(ExitCode::Faulted),
+            _ => bail!("Invalid exit code: {words:?}"),
+        }
+    }
+}
+
+// Checks the outputs of a seal against the journal, the digests of the states
+// the guest resumed from and paused in, and the exit code.
+fn check_outputs<S: risc0_zkp::core::sha::Sha>(
+    sha: &S,
+    outputs: &[u32],
+    journal: &[u32],
+    pre_state: Option<&Digest>,
+    post_state: Option<&Digest>,
+    exit_code: ExitCode,
+) -> Result<(), risc0_zkp::verify::VerificationError> {
+    use risc0_zkp::{
+        core::sha::DIGEST_WORDS,
+        verify::{check_journal, VerificationError},
+    };
+
+    let output_len = outputs[DIGEST_WORDS] as usize;
+    if output_len & EXIT_CODE_FLAG == 0 {
+        // Older guests only commit to the journal, and can only halt by
+        // returning from their main function.
+        if exit_code != ExitCode::Halted(0) || pre_state.is_some() || post_state.is_some() {
+            return Err(VerificationError::JournalSealRootMismatch);
+        }
+        return check_journal(sha, outputs, journal);
+    }
+    // Only a guest which paused commits to the state it paused in.
+    let resumed = output_len & RESUMED_FLAG != 0;
+    if resumed != pre_state.is_some() || (exit_code == ExitCode::Paused) != post_state.is_some() {
+        return Err(VerificationError::JournalSealRootMismatch);
+    }
+    let mut outputs = outputs.to_vec();
+    outputs[DIGEST_WORDS] = (output_len & !(EXIT_CODE_FLAG | RESUMED_FLAG)) as u32;
+    let mut committed = Vec::with_capacity(journal.len() + 2 * DIGEST_WORDS + ExitCode::WORDS);
+    committed.extend_from_slice(journal);
+    for digest in [pre_state, post_state].into_iter().flatten() {
+        committed.extend_from_slice(digest.get());
+    }
+    committed.extend_from_slice(&exit_code.to_words());
+    check_journal(sha, &outputs, &committed)
+}
+
+pub fn verify_with_hal<H, M>(
+    hal: &H,
+    method_id: M,
+    seal: &[u32],
+    journal: &[u32],
+    exit_code: ExitCode,
+) -> Result<()>
+where
+    H: risc0_zkp::verify::VerifyHal,
+    MethodId: From<M>,
+{
+    verify_with_hal_and_max_po2(hal, method_id, seal, journal, exit_code, MAX_CYCLES_PO2)
+}
+
+/// Like [verify_with_hal], but also rejects receipts for executions with a
+/// trace larger than `2^max_po2` cycles.
+pub fn verify_with_hal_and_max_po2<H, M>(
+    hal: &H,
+    method_id: M,
+    seal: &[u32],
+    journal: &[u32],
+    exit_code: ExitCode,
+    max_po2: usize,
+) -> Result<()>
+where
+    H: risc0_zkp::verify::VerifyHal,
+    MethodId: From<M>,
+{
+    verify_segment(
+        hal, method_id, seal, journal, None, None, exit_code, max_po2,
+    )
+}
+
+// Verifies the seal of a segment of an execution, which may have resumed from
+// or paused in the states with the given digests.
+#[allow(clippy::too_many_arguments)]
+fn verify_segment<H, M>(
+    hal: &H,
+    method_id: M,
+    seal: &[u32],
+    journal: &[u32],
+    pre_state: Option<&Digest>,
+    post_state: Option<&Digest>,
+    exit_code: ExitCode,
+    max_po2: usize,
+) -> Result<()>
 where
     H: risc0_zkp::verify::VerifyHal,
     MethodId: From<M>,
@@ -37,6 +181,9 @@ where
     let method_id: MethodId = method_id.into();
     let check_code = |po2: u32, merkle_root: &Digest| -> Result<(), VerificationError> {
         let po2 = po2 as usize;
+        if po2 > max_po2 {
+            return Err(VerificationError::Po2LimitExceeded { po2, max_po2 });
+        }
         let which = po2 - MIN_CYCLES_PO2;
         #[cfg(not(target_os = "zkvm"))]
         if log::log_enabled!(log::Level::Debug) {
@@ -62,15 +209,25 @@ where
         return Ok(());
     }
 
-    risc0_zkp::verify::verify(hal, &CIRCUIT, seal, journal, check_code)
-        .map_err(|err| anyhow!("Verification failed: {}", err))
+    risc0_zkp::verify::verify_with_outputs(hal, &CIRCUIT, seal, check_code, |outputs| {
+        check_outputs(
+            hal.sha(),
+            outputs,
+            journal,
+            pre_state,
+            post_state,
+            exit_code,
+        )
+    })
+    .map_err(|err| anyhow!("Verification failed: {}", err))
 }
 
 impl Receipt {
-    pub fn new(journal: &[u32], seal: &[u32]) -> Self {
+    pub fn new(journal: &[u32], seal: &[u32], exit_code: ExitCode) -> Self {
         Self {
             journal: Vec::from(journal),
             seal: Vec::from(seal),
+            exit_code,
         }
     }
 
@@ -82,7 +239,27 @@ impl Receipt {
         let sha = crate::sha::sha();
         let hal = risc0_zkp::verify::CpuVerifyHal::new(sha, &crate::CIRCUIT);
 
-        verify_with_hal(&hal, method_id, &self.seal, &self.journal)
+        self.verify_with_hal(&hal, method_id)
+    }
+
+    /// Verifies the receipt, rejecting it if the execution used a trace
+    /// larger than `2^max_po2` cycles.
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn verify_with_max_po2<M>(&self, method_id: M, max_po2: usize) -> Result<()>
+    where
+        MethodId: From<M>,
+    {
+        let sha = crate::sha::sha();
+        let hal = risc0_zkp::verify::CpuVerifyHal::new(sha, &crate::CIRCUIT);
+
+        verify_with_hal_and_max_po2(
+            &hal,
+            method_id,
+            &self.seal,
+            &self.journal,
+            self.exit_code,
+            max_po2,
+        )
     }
 
     pub fn verify_with_hal<H, M>(&self, hal: &H, method_id: M) -> Result<()>
@@ -90,7 +267,7 @@ impl Receipt {
         H: risc0_zkp::verify::VerifyHal,
         MethodId: From<M>,
     {
-        verify_with_hal(hal, method_id, &self.seal, &self.journal)
+        verify_with_hal(hal, method_id, &self.seal, &self.journal, self.exit_code)
     }
 
     pub fn get_journal_bytes(&self) -> &[u8] {
@@ -101,3 +278,111 @@ impl Receipt {
         bytemuck::cast_slice(self.seal.as_slice())
     }
 }
+
+/// A receipt for one segment of an execution which was proven in several
+/// segments.  Every segment but the last pauses, committing to the digest of
+/// the state it paused in, and every segment but the first resumes from the
+/// state the one before it paused in.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SegmentReceipt {
+    /// The digest of the state this segment resumed from, if it did.
+    pub pre_state: Option<Digest>,
+    /// The digest of the state this segment paused in, if it did.
+    pub post_state: Option<Digest>,
+    /// The journal, which is only committed by the last segment.
+    pub journal: Vec<u32>,
+    pub seal: Vec<u32>,
+    pub exit_code: ExitCode,
+}
+
+impl SegmentReceipt {
+    /// Verifies this segment on its own, without checking that it follows on
+    /// from any other segment.
+    pub fn verify_with_hal<H, M>(&self, hal: &H, method_id: M) -> Result<()>
+    where
+        H: risc0_zkp::verify::VerifyHal,
+        MethodId: From<M>,
+    {
+        verify_segment(
+            hal,
+            method_id,
+            &self.seal,
+            &self.journal,
+            self.pre_state.as_ref(),
+            self.post_state.as_ref(),
+            self.exit_code,
+            MAX_CYCLES_PO2,
+        )
+    }
+}
+
+/// A receipt for an execution which was proven in several segments, each of
+/// which is limited to a trace of `2^MAX_CYCLES_PO2` cycles.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SessionReceipt {
+    pub segments: Vec<SegmentReceipt>,
+}
+
+impl SessionReceipt {
+    /// Returns the journal, which the last segment commits to.
+    pub fn journal(&self) -> &[u32] {
+        self.segments
+            .last()
+            .map_or(&[], |segment| segment.journal.as_slice())
+    }
+
+    pub fn get_journal_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(self.journal())
+    }
+
+    /// Returns how the guest stopped at the end of the last segment.
+    pub fn exit_code(&self) -> Option<ExitCode> {
+        self.segments.last().map(|segment| segment.exit_code)
+    }
+
+    #[cfg(not(target_os = "zkvm"))]
+    pub fn verify<M>(&self, method_id: M) -> Result<()>
+    where
+        MethodId: From<M>,
+    {
+        let sha = crate::sha::sha();
+        let hal = risc0_zkp::verify::CpuVerifyHal::new(sha, &crate::CIRCUIT);
+
+        self.verify_with_hal(&hal, method_id)
+    }
+
+    /// Verifies each segment, and that the segments chain together: the
+    /// first starts the guest afresh, each of the others resumes from the
+    /// state the one before it paused in, and the last doesn't pause.
+    pub fn verify_with_hal<H, M>(&self, hal: &H, method_id: M) -> Result<()>
+    where
+        H: risc0_zkp::verify::VerifyHal,
+        MethodId: From<M>,
+    {
+        let method_id: MethodId = method_id.into();
+        let (last, init) = match self.segments.split_last() {
+            Some(split) => split,
+            None => bail!("Session has no segments"),
+        };
+        let mut pre_state = None;
+        for (i, segment) in self.segments.iter().enumerate() {
+            if segment.pre_state != pre_state {
+                bail!("Segment {i} doesn't resume from the state the one before it paused in");
+            }
+            pre_state = segment.post_state;
+        }
+        if let Some(i) = init
+            .iter()
+            .position(|segment| segment.exit_code != ExitCode::Paused)
+        {
+            bail!("Segment {i} stopped before the end of the session");
+        }
+        if last.exit_code == ExitCode::Paused {
+            bail!("Session ends with a paused segment");
+        }
+        for segment in self.segments.iter() {
+            segment.verify_with_hal(hal, &method_id)?;
+        }
+        Ok(())
+    }
+}
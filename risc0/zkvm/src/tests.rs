@@ -16,19 +16,28 @@ use std::{fmt, sync::Mutex};
 
 use anyhow::Result;
 use risc0_zeroio::{from_slice, to_vec};
-use risc0_zkp::core::sha::Digest;
+use risc0_zkp::{core::sha::Digest, MAX_CYCLES_PO2, MIN_CYCLES_PO2};
 use risc0_zkvm_methods::{
-    multi_test::MultiTestSpec, FIB_CONTENTS, FIB_ID, MULTI_TEST_CONTENTS, MULTI_TEST_ID,
+    multi_test::MultiTestSpec, FIB_CONTENTS, FIB_ID, HEAP_CHURN_CONTENTS, HEAP_CHURN_ID,
+    MULTI_TEST_CONTENTS, MULTI_TEST_ID,
 };
 use risc0_zkvm_platform::{
-    memory::{COMMIT, HEAP},
+    memory::{COMMIT, HEAP, MEM_SIZE, STACK, SYSTEM},
+    syscall::{
+        ecall,
+        nr::SYS_USER_BASE,
+        reg_abi::{REG_A0, REG_A1},
+    },
     WORD_SIZE,
 };
 use serial_test::serial;
 use test_log::test;
 
-use super::{MethodId, Prover, ProverOpts, Receipt};
-use crate::prove::TraceEvent;
+use super::{
+    ExecutionError, ExitCode, HostChannel, MemoryFaultKind, MethodId, ProveEvent, ProvePhase,
+    Prover, ProverOpts, ProverPool, Receipt, SessionReceipt, Snapshot, SyscallContext,
+};
+use crate::{prove::TraceEvent, trace::Replay};
 
 #[test]
 #[serial]
@@ -77,6 +86,18 @@ fn unwrap_err<T, E: fmt::Debug>(result: Result<T, E>) -> String {
     }
 }
 
+// Runs the prover to completion, either by executing the guest or by
+// proving it and verifying the seal, and returns the journal.
+fn run_journal(prover: &mut Prover, prove: bool) -> Vec<u32> {
+    if prove {
+        let receipt = prover.run().unwrap();
+        receipt.verify(MULTI_TEST_ID).unwrap();
+        receipt.journal
+    } else {
+        prover.execute().unwrap().journal
+    }
+}
+
 #[test]
 #[serial]
 fn memory_io() {
@@ -142,14 +163,800 @@ fn receipt_serde_no_seal() {
 }
 
 #[test]
+#[serial]
 fn fail() {
-    // Check that a compliant host will fault.
+    // A guest which panics still gets a receipt, which proves that it
+    // panicked.
     let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
     prover.add_input_u32_slice(&to_vec(&MultiTestSpec::Fail).unwrap());
+    let mut receipt = prover.run().unwrap();
+    assert_eq!(receipt.exit_code, ExitCode::Faulted);
+    receipt.verify(MULTI_TEST_ID).unwrap();
 
-    assert!(unwrap_err(prover.run())
-        .to_string()
-        .contains("MultiTestSpec::Fail invoked"));
+    receipt.exit_code = ExitCode::Halted(0);
+    receipt.verify(MULTI_TEST_ID).unwrap_err();
+}
+
+#[test]
+fn sha_stream() {
+    use sha2::{Digest as _, Sha256};
+
+    for len in [0, 55, 56, 64, 1000] {
+        let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        let expected = Sha256::digest(&data).to_vec();
+        for chunk_size in [1, 13, 64] {
+            let input = to_vec(&MultiTestSpec::ShaStream {
+                data: data.clone(),
+                chunk_size,
+            })
+            .unwrap();
+            let result = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input)).unwrap();
+            let hash: Vec<u8> = crate::serde::from_slice(&result.journal).unwrap();
+            assert_eq!(hash, expected, "len: {len}, chunk_size: {chunk_size}");
+        }
+    }
+}
+
+#[test]
+fn execute() {
+    let input = to_vec(&MultiTestSpec::ShaDigest { data: "abc".into() }).unwrap();
+    let opts = ProverOpts::default().with_skip_seal(true);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&input);
+    let receipt = prover.run().unwrap();
+
+    let result = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input)).unwrap();
+    assert_eq!(result.journal, receipt.journal);
+    assert!(result.cycles < 1 << result.po2);
+}
+
+// Checks that execute-only mode estimates the trace size a run of the given
+// spec needs exactly, and the number of cycles to within 0.1%.
+fn check_estimate(spec: MultiTestSpec, stdin: &[u8]) {
+    let input = to_vec(&spec).unwrap();
+    let run = |max_po2| -> Result<(Vec<u32>, usize)> {
+        let opts = ProverOpts::default()
+            .with_skip_seal(true)
+            .with_max_po2(max_po2);
+        let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts)?;
+        prover.add_input_u32_slice(&input);
+        prover.add_input_reader(stdin);
+        let receipt = prover.run()?;
+        Ok((receipt.journal, prover.cycles))
+    };
+
+    let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+    prover.add_input_u32_slice(&input);
+    prover.add_input_reader(stdin);
+    let estimate = prover.execute().unwrap();
+
+    let (journal, cycles) = run(estimate.po2).unwrap();
+    assert_eq!(estimate.journal, journal);
+    assert!(
+        estimate.cycles.abs_diff(cycles) <= cycles / 1000,
+        "estimated {} cycles, but the run used {cycles}",
+        estimate.cycles
+    );
+    assert!(run(estimate.po2 - 1).is_err(), "po2: {}", estimate.po2);
+}
+
+#[test]
+fn execute_estimate_sha() {
+    let data: Vec<u8> = (0..16 * 1024).map(|i| i as u8).collect();
+    check_estimate(MultiTestSpec::ShaDigest { data }, &[]);
+}
+
+#[test]
+fn execute_estimate_divide() {
+    check_estimate(MultiTestSpec::Divide { count: 10000 }, &[]);
+}
+
+#[test]
+fn execute_estimate_syscalls() {
+    // Reads stdin a byte at a time.
+    let data = [1u8; 4096];
+    check_estimate(MultiTestSpec::ReadStdin { chunk_size: 1 }, &data);
+}
+
+#[test]
+#[serial]
+fn execute_cycle_count() {
+    // The guest sees the same cycle counts, give or take the estimate's
+    // tolerance, in execute-only mode as when it's run.
+    let input = to_vec(&MultiTestSpec::CycleCount).unwrap();
+    let [run, executed] = [true, false].map(|prove| {
+        let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+        prover.add_input_u32_slice(&input);
+        crate::serde::from_slice::<u32>(&run_journal(&mut prover, prove)).unwrap()
+    });
+    assert!(executed.abs_diff(run) <= run / 1000, "{executed} != {run}");
+}
+
+#[test]
+#[serial]
+fn session() {
+    let input = to_vec(&MultiTestSpec::Checkpoint { rounds: 2 }).unwrap();
+    let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+    prover.add_input_u32_slice(&input);
+    let expected = prover.run().unwrap().journal;
+
+    // Pause at every checkpoint.
+    let opts = ProverOpts::default().with_segment_limit(0);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&input);
+    let session = prover.run_session().unwrap();
+    assert_eq!(session.segments.len(), 3);
+    assert_eq!(session.journal(), expected);
+    assert_eq!(session.exit_code(), Some(ExitCode::Halted(0)));
+    session.verify(MULTI_TEST_ID).unwrap();
+
+    // The segments only verify in order, starting afresh.
+    let mut reordered = session.clone();
+    reordered.segments.swap(1, 2);
+    reordered.verify(MULTI_TEST_ID).unwrap_err();
+    let truncated = SessionReceipt {
+        segments: session.segments[1..].to_vec(),
+    };
+    truncated.verify(MULTI_TEST_ID).unwrap_err();
+    let mut tampered = session.clone();
+    tampered.segments[1].pre_state = tampered.segments[2].pre_state;
+    tampered.segments[0].post_state = tampered.segments[2].pre_state;
+    tampered.verify(MULTI_TEST_ID).unwrap_err();
+
+    // A paused segment isn't a receipt for the whole execution.
+    let first = &session.segments[0];
+    Receipt::new(&first.journal, &first.seal, first.exit_code)
+        .verify(MULTI_TEST_ID)
+        .unwrap_err();
+}
+
+#[test]
+fn memory_stats() {
+    // Reads into a 4k buffer on the stack.
+    let input = to_vec(&MultiTestSpec::ReadStdin { chunk_size: 4096 }).unwrap();
+    let opts = ProverOpts::default().with_skip_seal(true);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&input);
+    prover.run().unwrap();
+    let executed = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input))
+        .unwrap()
+        .memory;
+
+    for stats in [prover.memory_stats(), &executed] {
+        // Committing the result allocates.
+        assert!(stats.heap_peak_words > 0, "{stats:?}");
+        assert!(stats.heap_peak_words < HEAP.len_words(), "{stats:?}");
+        assert!(stats.stack_peak_bytes() > 4096, "{stats:?}");
+        assert!(stats.stack_peak_bytes() < STACK.len_bytes(), "{stats:?}");
+        for region in ["STACK", "HEAP", "INPUT", "PROG"] {
+            assert!(stats.pages_touched[region] > 0, "{region}: {stats:?}");
+        }
+    }
+    assert_eq!(
+        prover.memory_stats().heap_peak_words,
+        executed.heap_peak_words
+    );
+    assert_eq!(
+        prover.memory_stats().stack_low_water,
+        executed.stack_low_water
+    );
+}
+
+#[test]
+fn execute_fail() {
+    let input = to_vec(&MultiTestSpec::Fail).unwrap();
+    let result = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input)).unwrap();
+    assert_eq!(result.exit_code, ExitCode::Faulted);
+}
+
+#[test]
+fn execute_memory_fault() {
+    let addr = MEM_SIZE as u32;
+    let input = to_vec(&MultiTestSpec::ReadWriteMem {
+        values: vec![(addr, 0)],
+    })
+    .unwrap();
+    let err = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input)).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ExecutionError>(),
+        Some(&ExecutionError::MemoryFault {
+            addr,
+            kind: MemoryFaultKind::OutOfRange
+        })
+    );
+}
+
+#[test]
+fn run_faults() {
+    let input = to_vec(&MultiTestSpec::IllegalInstruction).unwrap();
+    for execute in [true, false] {
+        let opts = ProverOpts::default().with_skip_seal(true);
+        let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input);
+        let err = if execute {
+            prover.execute().unwrap_err()
+        } else {
+            prover.run().unwrap_err()
+        };
+        assert!(
+            matches!(
+                err.downcast_ref::<ExecutionError>(),
+                Some(ExecutionError::IllegalInstruction { word: 0, .. })
+            ),
+            "{err:?}"
+        );
+    }
+
+    // A syscall handler writing past the end of guest memory faults in the
+    // circuit's executor too.
+    let addr = MEM_SIZE as u32 - 4;
+    for execute in [true, false] {
+        let opts = ProverOpts::default().with_skip_seal(true).with_syscall(
+            SYS_FILL,
+            move |ctx: &mut dyn SyscallContext| -> Result<(u32, u32)> {
+                ctx.store_region(addr, &[1; 8])?;
+                Ok((0, 0))
+            },
+        );
+        let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+        prover.add_input_u32_slice(&to_vec(&MultiTestSpec::UserSyscall { nr: SYS_FILL }).unwrap());
+        let err = if execute {
+            prover.execute().unwrap_err()
+        } else {
+            prover.run().unwrap_err()
+        };
+        assert_eq!(
+            err.downcast_ref::<ExecutionError>(),
+            Some(&ExecutionError::MemoryFault {
+                addr,
+                kind: MemoryFaultKind::OutOfRange
+            }),
+            "{err:?}"
+        );
+    }
+
+    // So does a handler asking for a register that doesn't exist.
+    for execute in [true, false] {
+        let opts = ProverOpts::default().with_skip_seal(true).with_syscall(
+            SYS_FILL,
+            |ctx: &mut dyn SyscallContext| -> Result<(u32, u32)> {
+                ctx.load_register(32)?;
+                Ok((0, 0))
+            },
+        );
+        let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+        prover.add_input_u32_slice(&to_vec(&MultiTestSpec::UserSyscall { nr: SYS_FILL }).unwrap());
+        let err = if execute {
+            prover.execute().unwrap_err()
+        } else {
+            prover.run().unwrap_err()
+        };
+        assert!(format!("{err:#}").contains("Invalid register"), "{err:#}");
+    }
+}
+
+#[test]
+fn execute_snapshot_resume() {
+    let input = to_vec(&MultiTestSpec::ShaDigest { data: "abc".into() }).unwrap();
+    let expected = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input)).unwrap();
+
+    let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+    prover.add_input_u32_slice(&input);
+    assert_eq!(
+        prover.execute_until(expected.cycles / 2).unwrap().exit_code,
+        ExitCode::Paused
+    );
+
+    // Round trip the snapshot through its serialized form.
+    let snapshot = crate::serde::to_vec(&prover.snapshot().unwrap()).unwrap();
+    let snapshot: Snapshot = crate::serde::from_slice(&snapshot).unwrap();
+
+    let mut resumed = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+    resumed.add_input_u32_slice(&input);
+    resumed.resume_from(snapshot).unwrap();
+    let result = resumed.execute().unwrap();
+    assert_eq!(result.journal, expected.journal);
+    assert_eq!(result.cycles, expected.cycles);
+    assert_eq!(result.memory, expected.memory);
+}
+
+#[test]
+fn execute_snapshot_resume_stdin() {
+    // Larger than the input region, so the guest reads it in many chunks.
+    let data: Vec<u8> = (0..1280 * 1024).map(|i| i as u8).collect();
+    let input = to_vec(&MultiTestSpec::ReadStdin { chunk_size: 4096 }).unwrap();
+    let new_prover = || {
+        let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+        prover.add_input_u32_slice(&input);
+        prover.add_input_reader(data.as_slice());
+        prover
+    };
+    let expected = new_prover().execute().unwrap();
+
+    let mut prover = new_prover();
+    assert_eq!(
+        prover.execute_until(expected.cycles / 2).unwrap().exit_code,
+        ExitCode::Paused
+    );
+    let snapshot = prover.snapshot().unwrap();
+    assert!(snapshot.stdin_pos > 0 && snapshot.stdin_pos < data.len() as u64);
+    let snapshot = crate::serde::to_vec(&snapshot).unwrap();
+    let snapshot: Snapshot = crate::serde::from_slice(&snapshot).unwrap();
+
+    // The resumed guest reads stdin from where it was paused.
+    let mut resumed = new_prover();
+    resumed.resume_from(snapshot).unwrap();
+    let result = resumed.execute().unwrap();
+    assert_eq!(result.journal, expected.journal);
+    assert_eq!(result.cycles, expected.cycles);
+    assert_eq!(result.memory, expected.memory);
+
+    // Resuming needs the part of stdin the guest already read.
+    let mut prover = new_prover();
+    prover.execute_until(expected.cycles / 2).unwrap();
+    let snapshot = prover.snapshot().unwrap();
+    let mut resumed = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+    resumed.add_input_u32_slice(&input);
+    resumed.add_input_reader(&data[..1000]);
+    resumed.resume_from(snapshot).unwrap();
+    assert!(unwrap_err(resumed.execute()).contains("Standard input ended"));
+}
+
+const SYS_FILL: u32 = SYS_USER_BASE + 1;
+
+fn user_syscall_prover(opts: ProverOpts<'static>) -> Prover<'static> {
+    let opts = opts.with_syscall(
+        SYS_FILL,
+        |ctx: &mut dyn SyscallContext| -> Result<(u32, u32)> {
+            let buf_ptr = ctx.load_register(REG_A0)?;
+            let buf_len = ctx.load_register(REG_A1)?;
+            let data: Vec<u8> = (1..=buf_len as u8).collect();
+            ctx.store_region(buf_ptr, &data)?;
+            Ok((buf_len, 0))
+        },
+    );
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&to_vec(&MultiTestSpec::UserSyscall { nr: SYS_FILL }).unwrap());
+    prover
+}
+
+#[test]
+fn execute_then_run() {
+    let input = to_vec(&MultiTestSpec::ShaDigest { data: "abc".into() }).unwrap();
+    let opts = ProverOpts::default().with_skip_seal(true);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&input);
+    let executed = prover.execute().unwrap();
+    let receipt = prover.run().unwrap();
+    assert_eq!(receipt.journal, executed.journal);
+    assert_eq!(prover.get_output(), executed.stdout);
+    assert_eq!(prover.execute().unwrap().journal, executed.journal);
+}
+
+#[test]
+fn run_rejects_snapshot() {
+    let input = to_vec(&MultiTestSpec::ShaDigest { data: "abc".into() }).unwrap();
+    let expected = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input)).unwrap();
+
+    let opts = ProverOpts::default().with_skip_seal(true);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&input);
+    assert_eq!(
+        prover.execute_until(expected.cycles / 2).unwrap().exit_code,
+        ExitCode::Paused
+    );
+    let snapshot = prover.snapshot().unwrap();
+    assert!(unwrap_err(prover.run()).contains("paused or resumed"));
+
+    let opts = ProverOpts::default().with_skip_seal(true);
+    let mut resumed = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    resumed.add_input_u32_slice(&input);
+    resumed.resume_from(snapshot).unwrap();
+    assert!(unwrap_err(resumed.run()).contains("paused or resumed"));
+
+    // Finishing the execution clears the way for a fresh proof.
+    assert_eq!(resumed.execute().unwrap().journal, expected.journal);
+    assert_eq!(resumed.run().unwrap().journal, expected.journal);
+}
+
+#[test]
+fn user_syscall() {
+    let expected: (u32, [u32; 4]) = (16, [0x04030201, 0x08070605, 0x0c0b0a09, 0x100f0e0d]);
+    for execute in [true, false] {
+        let mut prover = user_syscall_prover(ProverOpts::default().with_skip_seal(true));
+        let journal = if execute {
+            prover.execute().unwrap().journal
+        } else {
+            prover.run().unwrap().journal
+        };
+        let result: (u32, [u32; 4]) = crate::serde::from_slice(&journal).unwrap();
+        assert_eq!(result, expected);
+    }
+}
+
+#[test]
+#[serial]
+fn user_syscall_sealed() {
+    // The handler writes into guest memory, which the seal must still cover.
+    let receipt = user_syscall_prover(ProverOpts::default()).run().unwrap();
+    receipt.verify(MULTI_TEST_ID).unwrap();
+    let result: (u32, [u32; 4]) = crate::serde::from_slice(&receipt.journal).unwrap();
+    assert_eq!(
+        result,
+        (16, [0x04030201, 0x08070605, 0x0c0b0a09, 0x100f0e0d])
+    );
+}
+
+#[test]
+#[serial]
+fn trace_file_replay() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("guest.trace");
+    let opts = ProverOpts::default()
+        .with_skip_seal(true)
+        .with_trace_file(&path)
+        .unwrap()
+        .with_syscall(
+            SYS_FILL,
+            |ctx: &mut dyn SyscallContext| -> Result<(u32, u32)> {
+                let buf_ptr = ctx.load_register(REG_A0)?;
+                let buf_len = ctx.load_register(REG_A1)?;
+                ctx.store_region(buf_ptr, &vec![7; buf_len as usize])?;
+                Ok((buf_len, 0))
+            },
+        );
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&to_vec(&MultiTestSpec::UserSyscall { nr: SYS_FILL }).unwrap());
+    let expected = prover.run().unwrap().journal;
+
+    // The trace is complete as soon as the run finishes, and replaying it
+    // doesn't need the input or the syscall handler.
+    let receipt = Replay::open(&path)
+        .unwrap()
+        .run(MULTI_TEST_CONTENTS)
+        .unwrap();
+    assert_eq!(receipt.journal, expected);
+
+    // A replay can be sealed, too.
+    let receipt = Replay::open(&path)
+        .unwrap()
+        .run_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, ProverOpts::default())
+        .unwrap();
+    receipt.verify(MULTI_TEST_ID).unwrap();
+    assert_eq!(receipt.journal, expected);
+
+    // A truncated trace can't be replayed.
+    let data = std::fs::read(&path).unwrap();
+    let truncated = data[..data.len() / 2].to_vec();
+    let err = Replay::new(std::io::Cursor::new(truncated))
+        .unwrap()
+        .run(MULTI_TEST_CONTENTS)
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("trace"), "{err:#}");
+
+    // Nor can a trace with records the guest never reaches.
+    let mut extended = data.clone();
+    extended.extend_from_slice(&data[8..]);
+    let err = Replay::new(std::io::Cursor::new(extended))
+        .unwrap()
+        .run(MULTI_TEST_CONTENTS)
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("unconsumed records"), "{err:#}");
+}
+
+#[test]
+#[serial]
+fn host_random() {
+    use rand::{rngs::mock::StepRng, RngCore};
+
+    let run = |rng: Option<StepRng>, prove: bool| {
+        let input = to_vec(&MultiTestSpec::Random { len: 16 }).unwrap();
+        let mut opts = ProverOpts::default();
+        if let Some(rng) = rng {
+            opts = opts.with_rng_source(rng);
+        }
+        let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input);
+        crate::serde::from_slice::<Vec<u8>>(&run_journal(&mut prover, prove)).unwrap()
+    };
+
+    let mut expected = vec![0; 16];
+    StepRng::new(1, 1).fill_bytes(&mut expected);
+    for prove in [false, true] {
+        assert_eq!(run(Some(StepRng::new(1, 1)), prove), expected);
+    }
+    assert_ne!(run(None, false), run(None, false));
+}
+
+#[test]
+#[serial]
+fn args_env() {
+    for prove in [false, true] {
+        let input = to_vec(&MultiTestSpec::ArgsEnv).unwrap();
+        let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+        prover.add_input_u32_slice(&input);
+        prover.add_arg("multi_test");
+        prover.add_arg("--verbose");
+        prover.add_arg("");
+        prover.set_env("FOO", "foo value");
+        let journal = run_journal(&mut prover, prove);
+        let (args, foo, bar): (Vec<String>, Option<String>, Option<String>) =
+            crate::serde::from_slice(&journal).unwrap();
+        assert_eq!(args, ["multi_test", "--verbose", ""]);
+        assert_eq!(foo.as_deref(), Some("foo value"));
+        assert_eq!(bar, None);
+    }
+}
+
+#[test]
+#[serial]
+fn read_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("data/sub")).unwrap();
+    std::fs::write(dir.path().join("data/table.txt"), "0123456789").unwrap();
+
+    type Files = (u32, [u8; 3], Vec<u8>, Vec<(String, bool)>, [bool; 3]);
+    let run = |prove: bool| {
+        let input = to_vec(&MultiTestSpec::ReadFiles).unwrap();
+        let opts = ProverOpts::default()
+            .with_fs_root(dir.path())
+            .with_file("config.txt", "key=value")
+            .unwrap()
+            .with_file("/data/extra.txt", "extra")
+            .unwrap();
+        let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input);
+        let files: Files = crate::serde::from_slice(&run_journal(&mut prover, prove)).unwrap();
+        (files, prover.file_reads())
+    };
+
+    for prove in [false, true] {
+        let ((len, part, config, entries, errors), reads) = run(prove);
+        assert_eq!(len, 10);
+        assert_eq!(&part, b"234");
+        assert_eq!(config, b"key=value");
+        assert_eq!(
+            entries,
+            [
+                ("extra.txt".to_string(), false),
+                ("sub".to_string(), true),
+                ("table.txt".to_string(), false),
+            ]
+        );
+        assert_eq!(errors, [true; 3]);
+        assert_eq!(
+            reads.into_iter().collect::<Vec<_>>(),
+            [
+                ("config.txt".to_string(), vec![0..9]),
+                ("data/table.txt".to_string(), vec![2..5]),
+            ]
+        );
+    }
+}
+
+#[test]
+fn heap_churn() {
+    // The guest allocates 32MB in total, which only fits in the 20MB heap if
+    // freed memory is reused.
+    let rounds = 512u32;
+    let mut prover = Prover::new_with_opts(
+        HEAP_CHURN_CONTENTS,
+        HEAP_CHURN_ID,
+        ProverOpts::default().with_skip_seal(true),
+    )
+    .unwrap();
+    prover.add_input_u32_slice(&crate::serde::to_vec(&rounds).unwrap());
+    let journal = prover.run().unwrap().journal;
+    let expected: u32 = (0..rounds).map(|round| round % 100).sum();
+    assert_eq!(crate::serde::from_slice::<u32>(&journal).unwrap(), expected);
+    assert!(prover.memory_stats().heap_peak_words * WORD_SIZE < 1024 * 1024);
+}
+
+// Guest logic for `run_native`: multiplies two numbers, exiting with 1 if
+// either is trivial.
+fn native_guest() {
+    use crate::guest::env;
+
+    let (a, b): (u64, u64) = env::read();
+    env::write(&"checking");
+    if a == 1 || b == 1 {
+        env::exit(1);
+    }
+    env::commit(&(a * b));
+}
+
+#[test]
+fn run_native() {
+    use crate::{serde::to_vec, testutil::run_native};
+
+    let result = run_native(native_guest, &to_vec(&(17u64, 23u64)).unwrap());
+    assert_eq!(result.exit_code, ExitCode::Halted(0));
+    assert_eq!(result.journal, to_vec(&391u64).unwrap());
+    let mut stdout = to_vec(&"checking").unwrap();
+    stdout.extend(&result.journal);
+    assert_eq!(result.stdout, bytemuck::cast_slice::<u32, u8>(&stdout));
+
+    let result = run_native(native_guest, &to_vec(&(1u64, 23u64)).unwrap());
+    assert_eq!(result.exit_code, ExitCode::Halted(1));
+    assert!(result.journal.is_empty());
+
+    // Panics in the guest reach the caller, and the guest environment is
+    // only available within a run.
+    assert!(std::panic::catch_unwind(|| run_native(native_guest, &[])).is_err());
+    assert!(std::panic::catch_unwind(|| crate::guest::env::read::<u32>()).is_err());
+}
+
+#[test]
+#[serial]
+fn read_zeroio() {
+    for prove in [false, true] {
+        let input = to_vec(&MultiTestSpec::ReadZeroio).unwrap();
+        let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+        prover.add_input_u32_slice(&input);
+        prover.add_input_zeroio(&"zero copy".to_string()).unwrap();
+        prover.add_input_zeroio(&vec![1u8, 2, 3, 250]).unwrap();
+        let journal = run_journal(&mut prover, prove);
+        let result: (String, u32, bool) = crate::serde::from_slice(&journal).unwrap();
+        assert_eq!(result, ("zero copy".to_string(), 256, true));
+    }
+
+    // The guest reads more values than were added.
+    let input = to_vec(&MultiTestSpec::ReadZeroio).unwrap();
+    let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+    prover.add_input_u32_slice(&input);
+    prover.add_input_zeroio(&"zero copy".to_string()).unwrap();
+    assert!(unwrap_err(prover.execute()).contains("more zeroio inputs"));
+}
+
+#[test]
+fn unknown_syscall() {
+    let input = to_vec(&MultiTestSpec::UserSyscall {
+        nr: SYS_USER_BASE + 2,
+    })
+    .unwrap();
+    let err = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input)).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ExecutionError>(),
+        Some(&ExecutionError::UnknownSyscall {
+            nr: SYS_USER_BASE + 2
+        })
+    );
+}
+
+#[test]
+fn prover_pool() {
+    use std::sync::Arc;
+
+    let pool = ProverPool::with_opts(2, 1, || ProverOpts::default().with_skip_seal(true));
+    let elf: Arc<[u8]> = MULTI_TEST_CONTENTS.into();
+    let method_id = MethodId::from(MULTI_TEST_ID);
+    let inputs: Vec<Vec<u8>> = ["a", "b", "c", "d"]
+        .iter()
+        .map(|data| {
+            let input = to_vec(&MultiTestSpec::ShaDigest {
+                data: (*data).into(),
+            })
+            .unwrap();
+            bytemuck::cast_slice(&input).to_vec()
+        })
+        .collect();
+    // More jobs than workers and queue slots, so submitting has to wait.
+    let handles: Vec<_> = inputs
+        .iter()
+        .map(|input| {
+            pool.submit(elf.clone(), method_id.clone(), input.clone())
+                .unwrap()
+        })
+        .collect();
+    let failing = pool
+        .submit(
+            elf.clone(),
+            method_id.clone(),
+            bytemuck::cast_slice(&to_vec(&MultiTestSpec::IllegalInstruction).unwrap()).to_vec(),
+        )
+        .unwrap();
+
+    for (input, handle) in inputs.iter().zip(handles) {
+        let expected = crate::execute(MULTI_TEST_CONTENTS, input).unwrap().journal;
+        assert_eq!(handle.wait().unwrap().journal, expected);
+    }
+    assert!(failing.wait().is_err());
+}
+
+#[test]
+#[serial]
+fn exit_code() {
+    let run = |spec| {
+        let input = to_vec(&spec).unwrap();
+        let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+        prover.add_input_u32_slice(&input);
+        prover.run().unwrap()
+    };
+
+    let receipt = run(MultiTestSpec::DoNothing);
+    assert_eq!(receipt.exit_code, ExitCode::Halted(0));
+    assert!(receipt.journal.is_empty());
+
+    let mut receipt = run(MultiTestSpec::Exit { code: 3 });
+    assert_eq!(receipt.exit_code, ExitCode::Halted(3));
+    assert_eq!(receipt.journal, [3]);
+    receipt.verify(MULTI_TEST_ID).unwrap();
+
+    let input = to_vec(&MultiTestSpec::Exit { code: 3 }).unwrap();
+    let result = crate::execute(MULTI_TEST_CONTENTS, bytemuck::cast_slice(&input)).unwrap();
+    assert_eq!(result.exit_code, ExitCode::Halted(3));
+    assert_eq!(result.journal, receipt.journal);
+
+    // The exit code is part of what the seal proves.
+    receipt.exit_code = ExitCode::Halted(0);
+    receipt.verify(MULTI_TEST_ID).unwrap_err();
+}
+
+#[test]
+#[serial]
+fn seeded_rng() {
+    let prove = |seed| {
+        let input = to_vec(&MultiTestSpec::DoNothing).unwrap();
+        let opts = ProverOpts::default().with_rng_seed(seed);
+        let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input);
+        prover.run().unwrap().seal
+    };
+    let seal = prove(1);
+    assert_eq!(seal, prove(1));
+    assert_ne!(seal, prove(2));
+}
+
+#[test]
+#[serial]
+fn progress() {
+    let events = Mutex::new(Vec::new());
+    let input = to_vec(&MultiTestSpec::DoNothing).unwrap();
+    let opts = ProverOpts::default().with_progress(|event| events.lock().unwrap().push(event));
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(&input);
+    prover.run().unwrap();
+    drop(prover);
+    let events = events.into_inner().unwrap();
+
+    let mut phases = Vec::new();
+    let mut fri_rounds = 0;
+    for event in events.iter() {
+        match event {
+            ProveEvent::PhaseStarted { phase } => phases.push(*phase),
+            ProveEvent::PhaseFinished { phase, .. } => {
+                assert_eq!(phases.last(), Some(phase), "{events:?}")
+            }
+            ProveEvent::TraceSize { po2, .. } => assert!(*po2 >= MIN_CYCLES_PO2),
+            ProveEvent::FriRound { round, .. } => {
+                assert_eq!(*round, fri_rounds);
+                fri_rounds += 1;
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(
+        phases,
+        [
+            ProvePhase::Execute,
+            ProvePhase::CodeCommit,
+            ProvePhase::DataCommit,
+            ProvePhase::Accumulate,
+            ProvePhase::AccumCommit,
+            ProvePhase::CheckPoly,
+            ProvePhase::CheckCommit,
+            ProvePhase::Mix,
+            ProvePhase::Fri,
+        ]
+    );
+    assert!(fri_rounds > 0);
+    assert!(matches!(
+        events.last(),
+        Some(ProveEvent::PhaseFinished {
+            phase: ProvePhase::Fri,
+            ..
+        })
+    ));
 }
 
 #[test]
@@ -179,6 +986,49 @@ fn check_code() {
     );
 }
 
+#[test]
+#[cfg_attr(feature = "insecure_skip_seal", ignore)]
+fn verify_max_po2() {
+    let receipt = run_do_nothing(ProverOpts::default()).unwrap();
+    receipt
+        .verify_with_max_po2(MULTI_TEST_ID, MAX_CYCLES_PO2)
+        .unwrap();
+    assert!(receipt
+        .verify_with_max_po2(MULTI_TEST_ID, MIN_CYCLES_PO2)
+        .expect_err("Verification should fail with a max po2 below the receipt's")
+        .to_string()
+        .contains("exceeds the maximum allowed po2"));
+}
+
+#[test]
+fn max_po2() {
+    let err = run_do_nothing(ProverOpts::default().with_max_po2(MIN_CYCLES_PO2)).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ExecutionError>(),
+        Some(&ExecutionError::CycleLimitExceeded {
+            max_po2: MIN_CYCLES_PO2
+        })
+    );
+}
+
+#[test]
+fn execute_max_cycles() {
+    let mut prover = Prover::new_with_opts(
+        FIB_CONTENTS,
+        FIB_ID,
+        ProverOpts::default().with_max_cycles(100_000),
+    )
+    .unwrap();
+    prover.add_input_u32_slice(&[1_000_000]);
+    let err = prover.execute().unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ExecutionError>(),
+        Some(&ExecutionError::CycleBudgetExceeded {
+            max_cycles: 100_000
+        })
+    );
+}
+
 #[test]
 #[cfg_attr(feature = "insecure_skip_seal", ignore)]
 fn long_fib() {
@@ -247,6 +1097,129 @@ fn host_sendrecv_callback_panic() {
     prover.run().unwrap();
 }
 
+// A channel which replies with the number of bytes it has seen so far, and
+// fails once it has seen too many.
+struct CountingChannel {
+    total: usize,
+    limit: usize,
+}
+
+impl HostChannel for CountingChannel {
+    fn send_recv(&mut self, _channel: u32, buf: &[u8]) -> Result<Vec<u8>> {
+        self.total += buf.len();
+        if self.total > self.limit {
+            anyhow::bail!("Channel limit of {} bytes exceeded", self.limit);
+        }
+        Ok((self.total as u32).to_le_bytes().to_vec())
+    }
+}
+
+#[test]
+fn host_channel() {
+    let mut channel = CountingChannel {
+        total: 0,
+        limit: 100,
+    };
+    let opts = ProverOpts::default().with_host_channel(5, &mut channel);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(
+        &to_vec(&MultiTestSpec::SendRecv {
+            channel_id: 5,
+            count: 3,
+        })
+        .unwrap(),
+    );
+    prover.execute().unwrap();
+    drop(prover);
+    // Each call after the first echoes back the 4 byte reply.
+    assert_eq!(channel.total, 8);
+}
+
+#[test]
+fn host_channel_error() {
+    let channel = CountingChannel { total: 0, limit: 0 };
+    let opts = ProverOpts::default().with_host_channel(5, channel);
+    let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+    prover.add_input_u32_slice(
+        &to_vec(&MultiTestSpec::SendRecv {
+            channel_id: 5,
+            count: 3,
+        })
+        .unwrap(),
+    );
+    let err = prover.execute().unwrap_err();
+    assert!(format!("{err:#}").contains("Channel limit of 0 bytes exceeded"));
+}
+
+#[test]
+fn read_stdin() {
+    // Larger than the input region, to make sure it's streamed in.
+    let data: Vec<u8> = (0..1280 * 1024).map(|i| i as u8).collect();
+    let sum = data
+        .iter()
+        .fold(0u32, |sum, byte| sum.wrapping_add(*byte as u32));
+    let input = to_vec(&MultiTestSpec::ReadStdin { chunk_size: 4096 }).unwrap();
+
+    let mut prover = Prover::new(MULTI_TEST_CONTENTS, MULTI_TEST_ID).unwrap();
+    prover.add_input_u32_slice(&input);
+    let (first, rest) = data.split_at(1000);
+    prover.add_input_reader(first);
+    prover.add_input_reader(rest);
+    let result = prover.execute().unwrap();
+    let (total, actual): (u32, u32) = crate::serde::from_slice(&result.journal).unwrap();
+    assert_eq!(total as usize, data.len());
+    assert_eq!(actual, sum);
+}
+
+#[cfg(feature = "gdb")]
+fn gdb_request(stream: &mut std::net::TcpStream, packet: &str) -> String {
+    use std::io::{Read, Write};
+
+    let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    write!(stream, "${packet}#{checksum:02x}").unwrap();
+    let mut reply = Vec::new();
+    let mut byte = [0];
+    loop {
+        stream.read_exact(&mut byte).unwrap();
+        match byte[0] {
+            b'+' | b'$' => {}
+            b'#' => break,
+            byte => reply.push(byte),
+        }
+    }
+    let mut checksum = [0; 2];
+    stream.read_exact(&mut checksum).unwrap();
+    stream.write_all(b"+").unwrap();
+    String::from_utf8(reply).unwrap()
+}
+
+#[cfg(feature = "gdb")]
+#[test]
+fn gdb_stub() {
+    use std::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = std::thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        assert_eq!(gdb_request(&mut stream, "?"), "S05");
+        let regs = gdb_request(&mut stream, "g");
+        assert_eq!(regs.len(), 33 * 8);
+        let pc = gdb_request(&mut stream, "p20");
+        assert_eq!(pc, regs[32 * 8..]);
+        let pc_addr = u32::from_str_radix(&pc, 16).unwrap().swap_bytes();
+        let insn = gdb_request(&mut stream, &format!("m{pc_addr:x},4"));
+        assert_eq!(insn.len(), 8);
+        assert_eq!(gdb_request(&mut stream, "s"), "S05");
+        assert_ne!(gdb_request(&mut stream, "p20"), pc);
+        gdb_request(&mut stream, "c")
+    });
+    let (stream, _) = listener.accept().unwrap();
+    let opts = ProverOpts::default().with_skip_seal(true).with_gdb(stream);
+    run_do_nothing(opts).unwrap();
+    assert_eq!(client.join().unwrap(), "W00");
+}
+
 #[test]
 fn sha_accel() {
     let opts = ProverOpts::default().with_skip_seal(true);
@@ -345,6 +1318,101 @@ fn profiler() {
     );
 }
 
+#[cfg(feature = "profiler")]
+#[test]
+fn profiler_folded_and_flamegraph() {
+    use crate::prove::profiler::{diff_folded, Profiler};
+
+    let mut prof = Profiler::new("multi_test.elf", MULTI_TEST_CONTENTS).unwrap();
+    {
+        let opts = ProverOpts::default()
+            .with_skip_seal(true)
+            .with_trace_callback(prof.make_trace_callback());
+        let mut prover = Prover::new_with_opts(MULTI_TEST_CONTENTS, MULTI_TEST_ID, opts).unwrap();
+        prover.add_input_u32_slice(&to_vec(&MultiTestSpec::Profiler).unwrap());
+        prover.run().unwrap();
+    }
+    prof.finalize();
+
+    let mut folded = Vec::new();
+    prof.write_folded(&mut folded).unwrap();
+    let folded = String::from_utf8(folded).unwrap();
+    assert!(
+        folded.contains("profile_test_func1;profile_test_func2 "),
+        "{folded}"
+    );
+
+    let mut svg = Vec::new();
+    prof.write_flamegraph("multi_test", &mut svg).unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    assert!(svg.starts_with("<?xml"));
+    assert!(svg.contains("profile_test_func2"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+
+    // Comparing a profile with itself shows no changes.
+    assert_eq!(diff_folded(&folded, &folded).unwrap(), vec![]);
+}
+
+#[cfg(feature = "profiler")]
+#[test]
+fn profiler_diff() {
+    use crate::prove::profiler::{diff_folded, FunctionDelta};
+
+    let before = "main;a 10\nmain;b 5\nmain 1\n";
+    let after = "main;a 10\nmain;b 8\nmain;c 2\nother;b 1\n";
+    assert_eq!(
+        diff_folded(before, after).unwrap(),
+        vec![
+            FunctionDelta {
+                name: "b".into(),
+                before: 5,
+                after: 9,
+            },
+            FunctionDelta {
+                name: "c".into(),
+                before: 0,
+                after: 2,
+            },
+            FunctionDelta {
+                name: "main".into(),
+                before: 1,
+                after: 0,
+            },
+        ]
+    );
+    assert!(diff_folded("main;a ten", "").is_err());
+}
+
+#[cfg(feature = "profiler")]
+#[test]
+fn coverage() {
+    use crate::prove::coverage::Coverage;
+
+    let mut coverage = Coverage::new(MULTI_TEST_CONTENTS).unwrap();
+    {
+        let opts = ProverOpts::default()
+            .with_skip_seal(true)
+            .with_trace_callback(coverage.make_trace_callback());
+        run_do_nothing(opts).unwrap();
+    }
+
+    let hits = coverage.line_hits();
+    let (_, guest_lines) = hits
+        .iter()
+        .find(|(file, _)| file.ends_with("multi_test.rs"))
+        .expect("Guest source should have line info");
+    // Some of the guest ran, but not all of it.
+    assert!(guest_lines.values().any(|count| *count > 0));
+    assert!(guest_lines.values().any(|count| *count == 0));
+
+    let lcov = coverage.to_lcov();
+    assert_eq!(
+        lcov.matches("SF:").count(),
+        lcov.matches("end_of_record").count()
+    );
+    assert!(lcov.contains("multi_test.rs\nDA:"));
+}
+
 #[test]
 fn trace() {
     let mut events: Vec<TraceEvent> = Vec::new();
@@ -357,7 +1425,19 @@ fn trace() {
 
         prover.run().unwrap();
     }
-    let occurances = events
+    // Look at the instruction, register and memory write events in order.
+    let writes: Vec<TraceEvent> = events
+        .into_iter()
+        .filter(|event| {
+            matches!(
+                event,
+                TraceEvent::InstructionStart { .. }
+                    | TraceEvent::RegisterSet { .. }
+                    | TraceEvent::MemorySet { .. }
+            )
+        })
+        .collect();
+    let occurances = writes
         .windows(4)
         .filter_map(|window| {
             if let &[TraceEvent::InstructionStart {
@@ -389,5 +1469,44 @@ fn trace() {
             }
         })
         .count();
-    assert_eq!(occurances, 1, "trace events: {:#?}", &events);
+    assert_eq!(occurances, 1, "trace events: {:#?}", &writes);
+}
+
+#[test]
+fn trace_syscalls() {
+    let mut events: Vec<TraceEvent> = Vec::new();
+    {
+        let opts = ProverOpts::default()
+            .with_skip_seal(true)
+            .with_trace_callback(|event| Ok(events.push(event)));
+        run_do_nothing(opts).unwrap();
+    }
+    let count = |pred: fn(&TraceEvent) -> bool| events.iter().filter(|event| pred(event)).count();
+    assert!(count(|event| matches!(event, TraceEvent::MemoryRead { .. })) > 0);
+    assert!(count(|event| matches!(event, TraceEvent::SyscallStart { .. })) > 0);
+    assert_eq!(
+        count(|event| matches!(event, TraceEvent::SyscallStart { .. })),
+        count(|event| matches!(event, TraceEvent::SyscallEnd { .. }))
+    );
+    assert_eq!(count(|event| matches!(event, TraceEvent::Halt { .. })), 1);
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, TraceEvent::Ecall { kind: ecall::HALT })));
+
+    let mut pages: Vec<u32> = events
+        .iter()
+        .filter_map(|event| match event {
+            TraceEvent::PageTouched { addr } => Some(*addr),
+            _ => None,
+        })
+        .collect();
+    let touched = pages.len();
+    pages.sort();
+    pages.dedup();
+    assert!(touched > 0);
+    assert_eq!(pages.len(), touched, "pages should only be touched once");
+    assert!(
+        pages.iter().all(|addr| (*addr as usize) < SYSTEM.start()),
+        "registers aren't guest memory"
+    );
 }
@@ -0,0 +1,51 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for testing guest code.
+
+use crate::receipt::ExitCode;
+
+/// The outcome of running guest code natively with [run_native].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NativeResult {
+    /// The data committed by the guest.
+    pub journal: Vec<u32>,
+    /// The data written by the guest to stdout, including the data it
+    /// committed.
+    pub stdout: Vec<u8>,
+    /// How the guest stopped.
+    pub exit_code: ExitCode,
+}
+
+/// Runs a guest's main function natively on the host, without proving or
+/// emulating it, so its logic can be unit tested quickly.
+///
+/// The functions in [guest::env](crate::guest::env) read from the given
+/// input, as supplied by [Prover::add_input_u32_slice](crate::Prover), and
+/// their output is collected in the result.  Panics in the guest are passed
+/// on to the caller.  Only the functions in `guest::env` are available
+/// natively; the rest of the guest API needs the zkVM.
+///
+/// Since the guest may keep `'static` borrows of its input, a copy of the
+/// input is leaked on each run.
+///
+/// ```ignore
+/// use risc0_zkvm::{serde::to_vec, testutil::run_native};
+///
+/// let result = run_native(my_guest::main, &to_vec(&(17u64, 23u64)).unwrap());
+/// assert_eq!(result.journal, to_vec(&391u64).unwrap());
+/// ```
+pub fn run_native(main_fn: fn(), input: &[u32]) -> NativeResult {
+    crate::guest::run_native(main_fn, input)
+}